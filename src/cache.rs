@@ -0,0 +1,334 @@
+use crate::consts::CACHE_DIR;
+use crate::goversion::File as GoFile;
+use crate::utils::dir_size;
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::{BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tracing::instrument;
+
+/// A cached archive
+#[derive(Debug, Clone)]
+pub struct CacheEntry {
+    pub sha256: String,
+    pub path: PathBuf,
+    pub size: u64,
+    pub used: SystemTime,
+}
+
+/// Content addressed archive cache, every archive lives in `<dir>/<sha256>/<filename>`
+#[derive(Debug, Clone)]
+pub struct ArchiveCache {
+    dir: PathBuf,
+}
+
+pub(crate) fn sha256_file(path: &Path) -> Result<String> {
+    let mut reader = BufReader::new(fs::File::open(path)?);
+    let mut hasher = Sha256::new();
+    let mut buf = [0u8; 64 * 1024];
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Hashes everything read through it
+pub(crate) struct Sha256Reader<R> {
+    inner: R,
+    hasher: Sha256,
+}
+
+impl<R: Read> Sha256Reader<R> {
+    pub(crate) fn new(inner: R) -> Self {
+        Self {
+            inner,
+            hasher: Sha256::new(),
+        }
+    }
+    /// Hex sha256 of the bytes read so far
+    pub(crate) fn finish(self) -> String {
+        format!("{:x}", self.hasher.finalize())
+    }
+}
+
+impl<R: Read> Read for Sha256Reader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+}
+
+/// Copies everything read through it into `out`
+pub(crate) struct TeeReader<R, W> {
+    inner: R,
+    out: W,
+}
+
+impl<R: Read, W: Write> TeeReader<R, W> {
+    pub(crate) fn new(inner: R, out: W) -> Self {
+        Self { inner, out }
+    }
+    pub(crate) fn into_parts(self) -> (R, W) {
+        (self.inner, self.out)
+    }
+}
+
+impl<R: Read, W: Write> Read for TeeReader<R, W> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.out.write_all(&buf[..n])?;
+        Ok(n)
+    }
+}
+
+impl ArchiveCache {
+    pub fn new() -> Result<Self> {
+        Self::at(CACHE_DIR.clone())
+    }
+    pub fn at(dir: PathBuf) -> Result<Self> {
+        fs::create_dir_all(&dir).context("Can't create cache dir")?;
+        Ok(Self { dir })
+    }
+    fn path_for(&self, file: &GoFile) -> PathBuf {
+        self.dir.join(file.sha256()).join(file.filename())
+    }
+    /// Where a download of `file` lands to go straight into the cache
+    pub(crate) fn target_for(&self, file: &GoFile) -> Result<PathBuf> {
+        let path = self.path_for(file);
+        fs::create_dir_all(path.parent().context("No parent")?)?;
+        Ok(path)
+    }
+    /// The cached archive for `file`, entries that fail verification are dropped
+    #[instrument(skip(self))]
+    pub fn get(&self, file: &GoFile) -> Option<PathBuf> {
+        let path = self.path_for(file);
+        if !path.is_file() {
+            return None;
+        }
+        if sha256_file(&path).ok()? != file.sha256() {
+            paris::warn!("Cached {} is corrupted, removing it", file.filename());
+            let _ = fs::remove_dir_all(path.parent()?);
+            return None;
+        }
+        let _ = fs::File::options()
+            .write(true)
+            .open(&path)
+            .and_then(|x| x.set_modified(SystemTime::now()));
+        Some(path)
+    }
+    /// Stores a copy of an already verified archive file
+    #[instrument(err, skip(self))]
+    pub fn put_file(&self, file: &GoFile, src: &Path) -> Result<PathBuf> {
+        let path = self.path_for(file);
+        let parent = path.parent().context("No parent")?;
+        fs::create_dir_all(parent)?;
+        let tmp = parent.join(format!(".{}.tmp", file.filename()));
+        fs::copy(src, &tmp)?;
+        fs::rename(&tmp, &path)?;
+        Ok(path)
+    }
+    /// Every cached archive, most recently used first
+    pub fn entries(&self) -> Result<Vec<CacheEntry>> {
+        let mut ret = Vec::new();
+        for dir in fs::read_dir(&self.dir)? {
+            let dir = dir?;
+            if !dir.file_type()?.is_dir() {
+                continue;
+            }
+            let sha256 = dir.file_name().to_string_lossy().to_string();
+            for f in fs::read_dir(dir.path())? {
+                let f = f?;
+                if f.file_name().to_string_lossy().starts_with('.') {
+                    continue;
+                }
+                let meta = f.metadata()?;
+                ret.push(CacheEntry {
+                    sha256: sha256.clone(),
+                    path: f.path(),
+                    size: meta.len(),
+                    used: meta.modified()?,
+                });
+            }
+        }
+        ret.sort_unstable_by_key(|x| std::cmp::Reverse(x.used));
+        Ok(ret)
+    }
+    /// Dirs of downloads that never finished, they only hold the dot files a resumable
+    /// download keeps next to its target
+    pub fn partials(&self) -> Result<Vec<PathBuf>> {
+        let mut ret = Vec::new();
+        for dir in fs::read_dir(&self.dir)? {
+            let dir = dir?;
+            if !dir.file_type()?.is_dir() {
+                continue;
+            }
+            let finished = fs::read_dir(dir.path())?
+                .filter_map(|x| x.ok())
+                .any(|x| !x.file_name().to_string_lossy().starts_with('.'));
+            if !finished {
+                ret.push(dir.path());
+            }
+        }
+        Ok(ret)
+    }
+    fn remove(&self, entry: &CacheEntry) -> Result<u64> {
+        fs::remove_dir_all(self.dir.join(&entry.sha256))
+            .with_context(|| format!("Failed to remove {}", entry.path.display()))?;
+        Ok(entry.size)
+    }
+    /// Keeps the `keep` most recently used archives, drops unfinished downloads
+    /// and returns the freed bytes
+    pub fn prune(&self, keep: usize) -> Result<u64> {
+        let mut freed = 0;
+        for dir in self.partials()? {
+            freed += dir_size(&dir)?;
+            fs::remove_dir_all(&dir)
+                .with_context(|| format!("Failed to remove {}", dir.display()))?;
+        }
+        let pruned: u64 = self
+            .entries()?
+            .iter()
+            .skip(keep)
+            .map(|x| self.remove(x))
+            .sum::<Result<u64>>()?;
+        Ok(freed + pruned)
+    }
+    pub fn clear(&self) -> Result<u64> {
+        self.prune(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    /// Empty cache in a temp dir that also holds the source archives
+    fn scratch() -> (TempDir, ArchiveCache) {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = ArchiveCache::at(dir.path().join("cache")).unwrap();
+        (dir, cache)
+    }
+
+    /// A source archive with `data` in `dir` and the list entry describing it
+    fn archive(dir: &Path, filename: &str, data: &[u8]) -> (PathBuf, GoFile) {
+        let src = dir.join(filename);
+        fs::write(&src, data).unwrap();
+        let file = serde_json::from_value(serde_json::json!({
+            "filename": filename,
+            "os": "linux",
+            "arch": "amd64",
+            "sha256": sha256_file(&src).unwrap(),
+            "size": data.len().to_string(),
+            "kind": "archive",
+        }))
+        .unwrap();
+        (src, file)
+    }
+
+    fn age(path: &Path, secs: u64) {
+        fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(secs))
+            .unwrap();
+    }
+
+    #[test]
+    fn tees_what_is_read() {
+        let mut tee = TeeReader::new(Sha256Reader::new(&b"archive"[..]), Vec::new());
+        let mut read = Vec::new();
+        tee.read_to_end(&mut read).unwrap();
+        let (hasher, copy) = tee.into_parts();
+        assert_eq!(copy, read);
+        assert_eq!(hasher.finish(), format!("{:x}", Sha256::digest(b"archive")));
+    }
+
+    #[test]
+    fn gets_stored_archives() {
+        let (dir, cache) = scratch();
+        let (src, file) = archive(dir.path(), "go1.21.5.linux-amd64.tar.gz", b"archive");
+        assert!(cache.get(&file).is_none());
+        let stored = cache.put_file(&file, &src).unwrap();
+        assert_eq!(cache.get(&file), Some(stored.clone()));
+        assert_eq!(fs::read(stored).unwrap(), b"archive");
+    }
+
+    #[test]
+    fn misses_other_checksums() {
+        let (dir, cache) = scratch();
+        let (src, file) = archive(dir.path(), "go1.21.5.linux-amd64.tar.gz", b"archive");
+        let (_, other) = archive(dir.path(), "go1.21.5.linux-amd64.tar.gz", b"other");
+        cache.put_file(&file, &src).unwrap();
+        assert!(cache.get(&other).is_none());
+    }
+
+    #[test]
+    fn drops_corrupted_archives() {
+        let (dir, cache) = scratch();
+        let (src, file) = archive(dir.path(), "go1.21.5.linux-amd64.tar.gz", b"archive");
+        let stored = cache.put_file(&file, &src).unwrap();
+        fs::write(&stored, b"tampered").unwrap();
+        assert!(cache.get(&file).is_none());
+        assert!(!stored.parent().unwrap().exists());
+    }
+
+    #[test]
+    fn prunes_least_recently_used() {
+        let (dir, cache) = scratch();
+        let (src, old) = archive(dir.path(), "go1.20.14.linux-amd64.tar.gz", b"old");
+        age(&cache.put_file(&old, &src).unwrap(), 3600);
+        let (src, new) = archive(dir.path(), "go1.21.5.linux-amd64.tar.gz", b"newer");
+        age(&cache.put_file(&new, &src).unwrap(), 60);
+        let entries = cache.entries().unwrap();
+        let names: Vec<_> = entries
+            .iter()
+            .map(|x| x.path.file_name().unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "go1.21.5.linux-amd64.tar.gz",
+                "go1.20.14.linux-amd64.tar.gz"
+            ]
+        );
+        assert_eq!(entries[0].size, 5);
+        // a hit makes the older archive the most recently used one
+        cache.get(&old).unwrap();
+        assert_eq!(cache.prune(1).unwrap(), 5);
+        assert!(cache.get(&new).is_none());
+        assert!(cache.get(&old).is_some());
+        assert_eq!(cache.clear().unwrap(), 3);
+        assert!(cache.entries().unwrap().is_empty());
+    }
+
+    #[test]
+    fn prunes_unfinished_downloads() {
+        let (dir, cache) = scratch();
+        let (src, file) = archive(dir.path(), "go1.21.5.linux-amd64.tar.gz", b"archive");
+        cache.put_file(&file, &src).unwrap();
+        let (_, partial) = archive(dir.path(), "go1.22.1.linux-amd64.tar.gz", b"partial");
+        let target = cache.target_for(&partial).unwrap();
+        let part_dir = target.parent().unwrap().to_path_buf();
+        fs::write(part_dir.join(".go1.22.1.linux-amd64.tar.gz.part"), b"par").unwrap();
+        fs::write(
+            part_dir.join(".go1.22.1.linux-amd64.tar.gz.part.json"),
+            b"{}",
+        )
+        .unwrap();
+        assert_eq!(cache.entries().unwrap().len(), 1);
+        assert_eq!(cache.partials().unwrap(), std::slice::from_ref(&part_dir));
+        assert_eq!(cache.prune(1).unwrap(), 5);
+        assert!(!part_dir.exists());
+        assert!(cache.get(&file).is_some());
+    }
+}
@@ -0,0 +1,63 @@
+use clap::{Parser, Subcommand};
+use indicatif::HumanBytes;
+
+use crate::cache::ArchiveCache;
+use anyhow::Result;
+
+/// Manage the downloaded archive cache
+#[derive(Debug, Clone, Parser)]
+pub(crate) struct Cache {
+    #[clap(subcommand)]
+    command: CacheCommand,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+enum CacheCommand {
+    /// List the cached archives, most recently used first
+    List,
+    /// Remove all but the most recently used archives and every unfinished download
+    Prune {
+        /// How many archives to keep
+        #[clap(long)]
+        keep: usize,
+    },
+    /// Remove every cached archive
+    Clear,
+}
+
+impl Cache {
+    pub(crate) fn run(self) -> Result<()> {
+        let cache = ArchiveCache::new()?;
+        match self.command {
+            CacheCommand::List => {
+                for e in cache.entries()? {
+                    let name = e
+                        .path
+                        .file_name()
+                        .map(|x| x.to_string_lossy().to_string())
+                        .unwrap_or_default();
+                    println!(
+                        "{:<40} {:>10}  {}",
+                        name,
+                        HumanBytes(e.size).to_string(),
+                        e.sha256
+                    );
+                }
+                let partials = cache.partials()?;
+                if !partials.is_empty() {
+                    paris::info!(
+                        "{} unfinished download(s), removed by prune and clear",
+                        partials.len()
+                    );
+                }
+            }
+            CacheCommand::Prune { keep } => {
+                paris::success!("Freed {}", HumanBytes(cache.prune(keep)?));
+            }
+            CacheCommand::Clear => {
+                paris::success!("Freed {}", HumanBytes(cache.clear()?));
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,17 @@
+use clap::Parser;
+
+use crate::envs::EnvManager;
+use anyhow::Result;
+
+/// Show the golang version in effect for the working directory
+#[derive(Debug, Clone, Parser)]
+pub(crate) struct Current {}
+
+impl Current {
+    pub(crate) fn run(self) -> Result<()> {
+        let mut manager = EnvManager::new()?;
+        let (env, source) = manager.active()?;
+        println!("{} ({})", env.version, source);
+        Ok(())
+    }
+}
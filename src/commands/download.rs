@@ -2,13 +2,14 @@ use std::path::PathBuf;
 
 use clap::Parser;
 use dialoguer::console::Term;
-use semver::Version;
 
-use crate::goversion::GoVersions;
+use crate::goversion::{File, GoVersions};
 // use crate::Result;
-use crate::consts::VERSION_LIST;
+use crate::consts::{ARCH, OS, RETRIES, VERSION_LIST};
+use crate::version_spec::VersionSpec;
 use crate::{ask_for_version, Downloaded};
 use anyhow::{Context, Result};
+use itertools::Itertools;
 
 /// Download golang version to file
 #[derive(Debug, Clone, Parser)]
@@ -16,40 +17,68 @@ pub(crate) struct Download {
     output: PathBuf,
     #[clap(short, long)]
     workers: Option<u8>,
+    /// Times a failed download is retried, with a growing wait in between
+    #[clap(long)]
+    retries: Option<u32>,
+    /// Version to download, e.g. 1.21.5, 1.21, ~1.21, >=1.20,<1.22, latest, oldstable or rc
     #[clap(long, conflicts_with("interactive"))]
-    version: Option<Version>,
+    version: Option<VersionSpec>,
     #[clap(short, long)]
     interactive: bool,
+    /// Operating systems to download for, e.g. linux,darwin,windows, defaults to this one
+    #[clap(long, value_delimiter = ',')]
+    os: Vec<String>,
+    /// Architectures to download for, e.g. amd64,arm64, defaults to this one
+    #[clap(long, value_delimiter = ',')]
+    arch: Vec<String>,
+    /// Kind of file to download, archive, installer or source
+    #[clap(long, default_value = "archive")]
+    kind: String,
 }
 
 impl Download {
-    pub(crate) fn run(self) -> Result<()> {
+    pub(crate) fn run(mut self) -> Result<()> {
         let workers = self.workers.unwrap_or(num_cpus::get() as u8);
+        let retries = self.retries.unwrap_or(*RETRIES);
         let term = Term::stdout();
         let versions = GoVersions::new(VERSION_LIST.clone())?;
         let golang = {
-            if let Some(vers) = self.version {
-                let chosen: crate::goversion::GoVersion = versions.chosen_version(vers)?;
+            if let Some(spec) = self.version {
+                let chosen: crate::goversion::GoVersion = spec.resolve(&versions)?;
                 chosen
             } else if self.interactive {
                 let vers = ask_for_version(&term, &versions)?;
                 let chosen: crate::goversion::GoVersion = versions.chosen_version(vers.parsed)?;
                 chosen
             } else {
-                versions.latest()
+                versions.latest()?
             }
         };
-        paris::info!(
-            "<b><blue>Downloading golang version {}</></b>",
-            &golang.version
-        );
-        let file_path = golang.download(Some(self.output), workers)?;
-        if let Downloaded::File { dir, vers: _ } = file_path {
-            let path_str = dir.to_str().context("Path cannot be converted to string")?;
-            paris::success!(
-                "<b><bright green>Golang has been downloaded to {}</></b>",
-                path_str
-            );
+        if self.os.is_empty() {
+            self.os.push(OS.to_string());
+        }
+        if self.arch.is_empty() {
+            self.arch.push(ARCH.to_string());
+        }
+        let files: Vec<&File> = self
+            .os
+            .iter()
+            .cartesian_product(&self.arch)
+            .map(|(os, arch)| golang.file_for(os, arch, &self.kind))
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .unique_by(|x| x.filename())
+            .collect();
+        for f in files {
+            paris::info!("<b><blue>Downloading {}</></b>", f.filename());
+            let file_path = golang.download_file(f, Some(self.output.clone()), workers, retries)?;
+            if let Downloaded::File { dir, vers: _ } = file_path {
+                let path_str = dir.to_str().context("Path cannot be converted to string")?;
+                paris::success!(
+                    "<b><bright green>Golang has been downloaded to {}</></b>",
+                    path_str
+                );
+            }
         }
         Ok(())
     }
@@ -0,0 +1,47 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+use directories::BaseDirs;
+
+use crate::envs::EnvManager;
+use crate::shell::Shell;
+use crate::version_spec::VersionSpec;
+use anyhow::{Context, Result};
+
+/// Print the environment for a golang version, e.g. `eval "$(go_version_manager env)"`
+#[derive(Debug, Clone, Parser)]
+pub(crate) struct Env {
+    /// Shell to print the exports for, guessed from $SHELL by default
+    #[clap(long, value_enum)]
+    shell: Option<Shell>,
+    /// Installed version, defaults to the one in effect for the working directory
+    version: Option<VersionSpec>,
+}
+
+impl Env {
+    pub(crate) fn run(self) -> Result<()> {
+        let shell = self.shell.unwrap_or_else(Shell::detect);
+        let mut manager = EnvManager::new()?;
+        let env = match self.version {
+            Some(spec) => {
+                manager.refresh()?;
+                spec.select(manager.available(), |x| &x.version)
+                    .cloned()
+                    .with_context(|| format!("No installed version matching {}", spec))?
+            }
+            None => manager.active()?.0,
+        };
+        let gopath = std::env::var_os("GOPATH")
+            .map(PathBuf::from)
+            .or_else(|| BaseDirs::new().map(|x| x.home_dir().join("go")))
+            .context("Can't find the home dir for GOPATH")?;
+        let mut paths = manager.unmanaged_path();
+        paths.insert(0, env.bin_dir());
+        let mut out = shell.set_path(&paths)?;
+        out.push_str(&shell.set("GOROOT", &env.path.to_string_lossy()));
+        out.push_str(&shell.set("GOPATH", &gopath.to_string_lossy()));
+        out.push_str(&shell.set("GOTOOLCHAIN", "local"));
+        print!("{}", out);
+        Ok(())
+    }
+}
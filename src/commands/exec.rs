@@ -0,0 +1,29 @@
+use clap::Parser;
+
+use crate::envs::EnvManager;
+use crate::version_spec::VersionSpec;
+use anyhow::{Context, Result};
+
+/// Run a command with the chosen golang version without switching to it
+#[derive(Debug, Clone, Parser)]
+pub(crate) struct Exec {
+    /// Installed version to run with, e.g. 1.20.14, 1.21 or latest
+    version: VersionSpec,
+    /// Command to run, after --
+    #[clap(last = true, required = true)]
+    command: Vec<String>,
+}
+
+impl Exec {
+    pub(crate) fn run(self) -> Result<()> {
+        let mut manager = EnvManager::new()?;
+        manager.refresh()?;
+        let env = self
+            .version
+            .select(manager.available(), |x| &x.version)
+            .with_context(|| format!("No installed version matching {}", self.version))?;
+        let (program, args) = self.command.split_first().context("No command given")?;
+        let out = env.command(program, args)?.unchecked().run()?;
+        quit::with_code(out.status.code().unwrap_or(1));
+    }
+}
@@ -0,0 +1,79 @@
+use std::path::Path;
+
+use clap::Parser;
+
+use crate::envs::EnvManager;
+use crate::project::Pin;
+use crate::shell::Shell;
+use anyhow::{Context, Result};
+
+/// Print shell code switching PATH and GOROOT to the pinned version on every cd
+///
+/// Add `eval "$(go_version_manager hook bash)"` to .bashrc, the same with zsh to .zshrc
+/// or `go_version_manager hook fish | source` to config.fish
+#[derive(Debug, Clone, Parser)]
+pub(crate) struct Hook {
+    #[clap(value_enum)]
+    shell: Shell,
+}
+
+impl Hook {
+    pub(crate) fn run(self) -> Result<()> {
+        let exe = std::env::current_exe().context("Can't find own executable")?;
+        print!("{}", self.shell.hook(&exe)?);
+        Ok(())
+    }
+}
+
+/// Print the PATH and GOROOT for the working directory, called by the hook
+#[derive(Debug, Clone, Parser)]
+pub(crate) struct HookEnv {
+    #[clap(value_enum)]
+    shell: Shell,
+}
+
+impl HookEnv {
+    pub(crate) fn run(self) -> Result<()> {
+        let shell = self.shell;
+        let mut manager = EnvManager::new()?;
+        let mut paths = manager.unmanaged_path();
+        let mut out = String::new();
+        let mut pinned = false;
+        match Pin::from_cwd() {
+            Ok(Some(pin)) => {
+                if pin
+                    .spec
+                    .select(manager.available(), |x| &x.version)
+                    .is_none()
+                {
+                    manager.refresh()?;
+                }
+                match pin.spec.select(manager.available(), |x| &x.version) {
+                    Some(env) => {
+                        paths.insert(0, env.bin_dir());
+                        out.push_str(&shell.set("GOROOT", &env.path.to_string_lossy()));
+                        pinned = true;
+                    }
+                    None => out.push_str(&shell.warn(format!(
+                        "go_version_manager: version {} is not installed",
+                        pin
+                    ))),
+                }
+            }
+            Ok(None) => {}
+            Err(e) => out.push_str(&shell.warn(format!("go_version_manager: {}", e))),
+        }
+        let goroot_managed = std::env::var_os("GOROOT")
+            .map(|x| manager.is_managed(Path::new(&x)))
+            .unwrap_or(false);
+        if !pinned && goroot_managed {
+            out.push_str(&shell.unset("GOROOT"));
+        }
+        match shell.set_path(&paths) {
+            Ok(x) => out.push_str(&x),
+            Err(e) => out.push_str(&shell.warn(format!("go_version_manager: {}", e))),
+        }
+        print!("{}", out);
+        Ok(())
+    }
+}
@@ -5,10 +5,14 @@ use clap::{Args, Subcommand};
 
 use crate::consts::{CONFIG_PATH, CURRENT_INSTALL, DEFAULT_INSTALL};
 // use crate::Result;
+use crate::consts::VERSION_LIST;
 use crate::goversion::{GoVersion, GoVersions};
+use crate::utils::get_local_version;
 use anyhow::Result;
 use figment::providers::{Env, Format, Serialized, Toml};
 use figment::Figment;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
 
 /// Initialize the config
 #[derive(Debug, Clone, Args)]
@@ -42,7 +46,7 @@ impl Init {
 
 #[derive(Debug, Clone, Subcommand)]
 enum ConfigSubCommands {
-    Init(Config),
+    Init(Box<Config>),
     Set,
     Get,
     List,
@@ -76,9 +80,6 @@ impl Config {
         let conf = fs::read_to_string(&path)?;
         Ok(toml::from_str(&conf)?)
     }
-    pub fn into_app(self) -> Result<App> {
-        App::new(self)
-    }
     pub fn new(install_path: PathBuf, config_path: PathBuf) -> Result<Self> {
         if config_path.exists() {
             return Self::from_file(config_path);
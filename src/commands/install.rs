@@ -1,61 +1,93 @@
-use std::path::PathBuf;
-
 use clap::Parser;
 use dialoguer::console::Term;
-use semver::Version;
 
 use crate::ask_for_version;
-use crate::config::Config;
-use crate::consts::{CONFIG_PATH, DEFAULT_INSTALL};
-use crate::goversion::{GoVersion, GoVersions};
-use crate::utils::{check_in_path, check_writable};
+use crate::consts::RETRIES;
+use crate::envs::EnvManager;
+use crate::goversion::{Downloaded, GoVersion};
+use crate::project::Pin;
+use crate::utils::check_writable;
+use crate::version_spec::VersionSpec;
 use anyhow::{anyhow, Context, Result};
+use std::path::PathBuf;
 
-/// Install the chosen or latest golang version
+/// Install the chosen, pinned or latest golang version
 #[derive(Debug, Clone, Parser)]
 pub(crate) struct Install {
-    #[clap(short, long)]
-    config_path: Option<PathBuf>,
-    #[clap(conflicts_with("config_path"))]
-    install_path: Option<PathBuf>,
     #[clap(short, long)]
     workers: Option<u8>,
+    /// Times a failed download is retried, with a growing wait in between
+    #[clap(long)]
+    retries: Option<u32>,
+    /// Version to install, e.g. 1.21.5, 1.21, ~1.21, >=1.20,<1.22, latest, oldstable or rc
     #[clap(long, conflicts_with("interactive"))]
-    version: Option<Version>,
+    version: Option<VersionSpec>,
     #[clap(short, long)]
     interactive: bool,
+    /// Install from a local archive instead of downloading, e.g. go1.21.5.linux-amd64.tar.gz
+    #[clap(long, conflicts_with_all(["version", "interactive"]))]
+    from_archive: Option<PathBuf>,
+    /// Expected sha256 of the archive, looked up in the cached version list when omitted
+    #[clap(long, requires("from_archive"))]
+    sha256: Option<String>,
 }
 
 impl Install {
     pub(crate) fn run(self) -> Result<()> {
-        let install_path = self.install_path.unwrap_or_else(|| DEFAULT_INSTALL.clone());
-        let config_path = self.config_path.unwrap_or_else(|| CONFIG_PATH.clone());
         let workers = self.workers.unwrap_or_else(|| num_cpus::get() as u8);
-        let c = Config::new(install_path, config_path)?;
-        let versions = GoVersions::new(c.list_path.clone())?;
+        let mut manager = EnvManager::new()?;
+        manager.refresh()?;
+        if let Some(archive) = &self.from_archive {
+            let versions =
+                match self.sha256 {
+                    Some(_) => manager.versions().ok(),
+                    None => Some(manager.versions().context(
+                        "Can't verify the archive without a version list, pass --sha256",
+                    )?),
+                };
+            let res = Downloaded::from_archive(archive, versions, self.sha256.as_deref())?;
+            return install(&mut manager, &res);
+        }
+        let versions = manager.versions()?;
         let golang = {
-            if let Some(vers) = self.version {
-                let chosen: GoVersion = versions.chosen_version(vers)?;
+            if let Some(spec) = self.version {
+                let chosen: GoVersion = spec.resolve(versions)?;
                 chosen
             } else if self.interactive {
                 let term = Term::stdout();
-                let vers = ask_for_version(&term, &versions)?;
+                let vers = ask_for_version(&term, versions)?;
                 let chosen: GoVersion = versions.chosen_version(vers.parsed)?;
                 chosen
+            } else if let Some(pin) = Pin::from_cwd()? {
+                paris::info!("Installing version {}", pin);
+                pin.spec.resolve(versions)?
             } else {
-                versions.latest()
+                versions.latest()?
             }
         };
-        if check_writable(c.install_path.parent().context("No parent")?)? {
-            let res = golang.download(None, workers)?;
-            res.unpack(&c.install_path, false)?;
-            let bin_path = &c.install_path.join("bin");
-            if !check_in_path(bin_path)? {
-                paris::info!("Directory {} not in PATH", bin_path.display());
-            }
-            Ok(())
-        } else {
-            Err(anyhow!("{} is not writable", c.install_path.display()))
+        if manager.find(&golang.parsed).is_some() {
+            paris::info!("Golang version {} is already installed", golang.parsed);
+            return Ok(());
+        }
+        if !check_writable(manager.env_dir())? {
+            return Err(anyhow!("{} is not writable", manager.env_dir().display()));
         }
+        let retries = self.retries.unwrap_or(*RETRIES);
+        let res = golang.download(None, workers, retries)?;
+        install(&mut manager, &res)
+    }
+}
+
+fn install(manager: &mut EnvManager, res: &Downloaded) -> Result<()> {
+    let env = manager.install(res)?;
+    paris::success!(
+        "<b><bright green>Golang {} has been installed to {}</></b>",
+        env.version,
+        env.path.display()
+    );
+    if manager.current().is_none() {
+        manager.switch(&env.version)?;
+        paris::info!("Now using golang version {}", env.version);
     }
+    Ok(())
 }
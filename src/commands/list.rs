@@ -0,0 +1,84 @@
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use clap::Parser;
+use indicatif::HumanBytes;
+use semver::Version;
+use serde::Serialize;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+use crate::envs::{EnvManager, InstalledEnv};
+use crate::utils::dir_size;
+use anyhow::Result;
+
+/// List the installed golang versions
+#[derive(Debug, Clone, Parser)]
+pub(crate) struct List {
+    /// Print the list as JSON
+    #[clap(long)]
+    json: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct ListEntry {
+    version: Version,
+    path: PathBuf,
+    current: bool,
+    size: u64,
+    installed: Option<String>,
+}
+
+impl ListEntry {
+    fn new(env: &InstalledEnv, current: bool) -> Result<Self> {
+        let meta = std::fs::metadata(&env.path)?;
+        let installed = meta
+            .created()
+            .or_else(|_| meta.modified())
+            .ok()
+            .and_then(format_date);
+        Ok(Self {
+            version: env.version.clone(),
+            path: env.path.clone(),
+            current,
+            size: dir_size(&env.path)?,
+            installed,
+        })
+    }
+}
+
+fn format_date(t: SystemTime) -> Option<String> {
+    OffsetDateTime::from(t).format(&Rfc3339).ok()
+}
+
+impl List {
+    pub(crate) fn run(self) -> Result<()> {
+        let mut manager = EnvManager::new()?;
+        manager.refresh()?;
+        let current = manager.current().map(|x| x.path.clone());
+        let entries = manager
+            .available()
+            .iter()
+            .map(|x| ListEntry::new(x, current.as_ref() == Some(&x.path)))
+            .collect::<Result<Vec<_>>>()?;
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&entries)?);
+            return Ok(());
+        }
+        if entries.is_empty() {
+            paris::info!("No golang versions installed");
+            return Ok(());
+        }
+        for e in entries {
+            println!(
+                "{} {:<10} {:>10}  {:<10}  {}",
+                if e.current { "*" } else { " " },
+                e.version,
+                HumanBytes(e.size).to_string(),
+                e.installed.as_deref().map_or("-", |x| &x[..10]),
+                e.path.display()
+            );
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,87 @@
+use clap::Parser;
+use semver::Version;
+
+use crate::consts::VERSION_LIST;
+use crate::envs::EnvManager;
+use crate::goversion::{parse_go_version, GoVersion, GoVersions};
+use anyhow::{Context, Result};
+
+/// List the golang versions available for download
+#[derive(Debug, Clone, Parser)]
+pub(crate) struct ListRemote {
+    /// Only show stable releases
+    #[clap(long, conflicts_with("unstable"))]
+    stable: bool,
+    /// Only show betas and release candidates
+    #[clap(long)]
+    unstable: bool,
+    /// Only show releases of the given minor version, e.g. 1.21
+    #[clap(long, value_parser = parse_minor)]
+    minor: Option<(u64, u64)>,
+    /// Only show versions newer or equal to the given one
+    #[clap(long, value_parser = parse_go_version)]
+    since: Option<Version>,
+}
+
+fn parse_minor(src: &str) -> Result<(u64, u64)> {
+    let src = src.strip_prefix("go").unwrap_or(src);
+    let (major, minor) = src.split_once('.').context("Expected MAJOR.MINOR")?;
+    Ok((major.parse()?, minor.parse()?))
+}
+
+impl ListRemote {
+    fn matches(&self, v: &GoVersion) -> bool {
+        if self.stable && !v.is_stable() || self.unstable && v.is_stable() {
+            return false;
+        }
+        if let Some((major, minor)) = self.minor {
+            if v.parsed.major != major || v.parsed.minor != minor {
+                return false;
+            }
+        }
+        match &self.since {
+            Some(since) => &v.parsed >= since,
+            None => true,
+        }
+    }
+    pub(crate) fn run(self) -> Result<()> {
+        let versions = GoVersions::new(VERSION_LIST.clone())?;
+        let installed = EnvManager::new()
+            .and_then(|mut x| {
+                x.refresh()?;
+                Ok(x)
+            })
+            .map(|x| {
+                x.available()
+                    .iter()
+                    .map(|x| x.version.clone())
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        let mut shown: Vec<&GoVersion> = versions
+            .versions
+            .iter()
+            .filter(|x| self.matches(x))
+            .collect();
+        shown.sort_unstable_by(|a, b| b.parsed.cmp(&a.parsed));
+        for v in shown {
+            let line = format!(
+                "{:<12} {:<8} {:<10} {}",
+                v.version,
+                if v.is_stable() { "stable" } else { "unstable" },
+                if v.wanted_file().is_ok() {
+                    "archive"
+                } else {
+                    "no-archive"
+                },
+                if installed.contains(&v.parsed) {
+                    "installed"
+                } else {
+                    ""
+                }
+            );
+            println!("{}", line.trim_end());
+        }
+        Ok(())
+    }
+}
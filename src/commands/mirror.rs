@@ -0,0 +1,97 @@
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+
+use crate::consts::{ARCH, OS, VERSION_LIST};
+use crate::goversion::GoVersions;
+use crate::mirror::{self, Selection};
+use crate::version_spec::VersionSpec;
+use anyhow::Result;
+
+/// Export archives for machines without internet access and serve them like go.dev/dl
+#[derive(Debug, Clone, Parser)]
+pub(crate) struct Mirror {
+    #[clap(subcommand)]
+    command: MirrorCommand,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+enum MirrorCommand {
+    /// Download the matching archives and a pruned version list into a dir
+    Export {
+        /// Versions to export, repeat it for more, e.g. --versions 1.21.x --versions '>=1.22, <1.24'
+        #[clap(long, default_value = "latest")]
+        versions: Vec<VersionSpec>,
+        /// Operating systems to export, defaults to this one
+        #[clap(long, value_delimiter = ',')]
+        os: Vec<String>,
+        /// Architectures to export, defaults to this one
+        #[clap(long, value_delimiter = ',')]
+        arch: Vec<String>,
+        #[clap(short, long)]
+        workers: Option<u8>,
+        dir: PathBuf,
+    },
+    /// Serve an exported dir over HTTP, point download_url at it to use it
+    Serve {
+        dir: PathBuf,
+        #[clap(long, default_value = "127.0.0.1:8080")]
+        addr: String,
+    },
+}
+
+impl Mirror {
+    pub(crate) fn run(self) -> Result<()> {
+        match self.command {
+            MirrorCommand::Export {
+                versions,
+                mut os,
+                mut arch,
+                workers,
+                dir,
+            } => {
+                if os.is_empty() {
+                    os.push(OS.to_string());
+                }
+                if arch.is_empty() {
+                    arch.push(ARCH.to_string());
+                }
+                let workers = workers.unwrap_or_else(|| num_cpus::get() as u8);
+                let list = GoVersions::new(VERSION_LIST.clone())?;
+                let selection = Selection { versions, os, arch };
+                let exported = mirror::export(&list, &selection, &dir, workers)?;
+                paris::success!("Exported {} versions to {}", exported.len(), dir.display());
+            }
+            MirrorCommand::Serve { dir, addr } => mirror::serve(&dir, &addr)?,
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_ranges_in_one_spec() {
+        let parsed = Mirror::try_parse_from([
+            "mirror",
+            "export",
+            "--versions",
+            ">=1.20,<1.22",
+            "--versions",
+            "1.23.x",
+            "out",
+        ])
+        .unwrap();
+        match parsed.command {
+            MirrorCommand::Export { versions, .. } => assert_eq!(
+                versions,
+                [
+                    ">=1.20, <1.22".parse::<VersionSpec>().unwrap(),
+                    "1.23.x".parse().unwrap()
+                ]
+            ),
+            other => panic!("{:?}", other),
+        }
+    }
+}
@@ -1,18 +1,52 @@
 use clap::Parser;
 use clap::Subcommand;
 
-use {completions::Completions, download::Download, init::Init, install::Install, update::Update};
+use {
+    cache::Cache,
+    completions::Completions,
+    current::Current,
+    download::Download,
+    env::Env,
+    exec::Exec,
+    hook::{Hook, HookEnv},
+    init::Init,
+    install::Install,
+    list::List,
+    list_remote::ListRemote,
+    mirror::Mirror,
+    refresh::Refresh,
+    rollback::Rollback,
+    shims::Shims,
+    uninstall::Uninstall,
+    update::Update,
+    use_version::Use,
+};
 
 // use crate::Result;
+use crate::consts::VERSION_LIST;
+use crate::goversion::GoVersions;
 use anyhow::Result;
 use shadow_rs::shadow;
 
 shadow!(build);
+mod cache;
 mod completions;
+mod current;
 mod download;
+mod env;
+mod exec;
+mod hook;
 mod init;
 mod install;
+mod list;
+mod list_remote;
+mod mirror;
+mod refresh;
+mod rollback;
+mod shims;
+mod uninstall;
 mod update;
+mod use_version;
 
 #[derive(Debug, Parser)]
 #[clap(name = "go_version_manager")]
@@ -22,12 +56,20 @@ mod update;
 ///
 /// Keep calm and carry on
 pub(crate) struct Opt {
+    /// Fetch the list of golang releases before running the command
+    #[clap(long, global = true)]
+    pub(crate) refresh: bool,
     #[clap(subcommand)]
     pub(crate) subcommand: Command,
 }
 
 impl Opt {
     pub(crate) fn run(self) -> Result<()> {
+        if self.refresh {
+            if let Err(e) = GoVersions::fetch(VERSION_LIST.clone()) {
+                paris::warn!("Failed to refresh the version list: {}", e);
+            }
+        }
         self.subcommand.run()
     }
 }
@@ -39,6 +81,21 @@ pub(crate) enum Command {
     Install(Install),
     Download(Download),
     Completions(Completions),
+    Use(Use),
+    List(List),
+    ListRemote(ListRemote),
+    Uninstall(Uninstall),
+    Current(Current),
+    Exec(Exec),
+    Shims(Shims),
+    Hook(Hook),
+    Env(Env),
+    Cache(Cache),
+    Refresh(Refresh),
+    Mirror(Mirror),
+    Rollback(Rollback),
+    #[clap(hide = true)]
+    HookEnv(HookEnv),
 }
 
 impl Command {
@@ -49,6 +106,20 @@ impl Command {
             Self::Update(u) => u.run(),
             Self::Completions(c) => c.run(),
             Self::Install(i) => i.run(),
+            Self::Use(u) => u.run(),
+            Self::List(l) => l.run(),
+            Self::ListRemote(l) => l.run(),
+            Self::Uninstall(u) => u.run(),
+            Self::Current(c) => c.run(),
+            Self::Exec(e) => e.run(),
+            Self::Shims(s) => s.run(),
+            Self::Hook(h) => h.run(),
+            Self::Env(e) => e.run(),
+            Self::Cache(c) => c.run(),
+            Self::Refresh(r) => r.run(),
+            Self::Mirror(m) => m.run(),
+            Self::Rollback(r) => r.run(),
+            Self::HookEnv(h) => h.run(),
         }
     }
 }
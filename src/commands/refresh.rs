@@ -0,0 +1,24 @@
+use clap::Parser;
+
+use crate::consts::VERSION_LIST;
+use crate::goversion::GoVersions;
+use anyhow::Result;
+
+/// Fetch the list of golang releases again, ignoring the cached copy
+#[derive(Debug, Clone, Parser)]
+pub(crate) struct Refresh {}
+
+impl Refresh {
+    pub(crate) fn run(self) -> Result<()> {
+        let versions = GoVersions::fetch(VERSION_LIST.clone())?;
+        match versions.latest() {
+            Ok(latest) => paris::success!(
+                "Fetched {} releases, latest is {}",
+                versions.len(),
+                latest.parsed
+            ),
+            Err(_) => paris::success!("Fetched {} releases, none of them stable", versions.len()),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,17 @@
+use clap::Parser;
+
+use crate::envs::EnvManager;
+use anyhow::Result;
+
+/// Switch back to the golang version used before the last switch
+#[derive(Debug, Clone, Parser)]
+pub(crate) struct Rollback {}
+
+impl Rollback {
+    pub(crate) fn run(self) -> Result<()> {
+        let mut manager = EnvManager::new()?;
+        let env = manager.rollback()?;
+        paris::success!("Rolled back to golang version {}", env.version);
+        Ok(())
+    }
+}
@@ -0,0 +1,34 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+
+use crate::consts::SHIMS_DIR;
+use crate::shims::create;
+use crate::utils::check_in_path;
+use anyhow::Result;
+
+/// Create go and gofmt shims that run the version in effect for the working directory
+#[derive(Debug, Clone, Parser)]
+pub(crate) struct Shims {
+    /// Where to put the shims
+    #[clap(long)]
+    dir: Option<PathBuf>,
+}
+
+impl Shims {
+    pub(crate) fn run(self) -> Result<()> {
+        let dir = self.dir.unwrap_or_else(|| SHIMS_DIR.clone());
+        create(&dir)?;
+        paris::success!(
+            "<b><bright green>Shims have been created in {}</></b>",
+            dir.display()
+        );
+        if !check_in_path(&dir)? {
+            paris::info!(
+                "Directory {} not in PATH, add it in front of any other go",
+                dir.display()
+            );
+        }
+        Ok(())
+    }
+}
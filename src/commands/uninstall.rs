@@ -0,0 +1,49 @@
+use clap::Parser;
+use indicatif::HumanBytes;
+
+use crate::envs::EnvManager;
+use crate::version_spec::VersionSpec;
+use anyhow::{anyhow, Context, Result};
+
+/// Remove installed golang versions
+#[derive(Debug, Clone, Parser)]
+pub(crate) struct Uninstall {
+    /// Installed versions to remove, e.g. 1.21.5, go1.21.5 or 1.20 for the newest 1.20.x
+    #[clap(required = true)]
+    versions: Vec<VersionSpec>,
+    /// Remove the version even if it's the current one
+    #[clap(short, long)]
+    force: bool,
+}
+
+impl Uninstall {
+    pub(crate) fn run(self) -> Result<()> {
+        let mut manager = EnvManager::new()?;
+        manager.refresh()?;
+        let mut reclaimed = 0;
+        let mut failed = 0;
+        for spec in &self.versions {
+            let res = spec
+                .select(manager.available(), |x| &x.version)
+                .map(|x| x.version.clone())
+                .with_context(|| format!("No installed version matching {}", spec))
+                .and_then(|v| Ok((manager.uninstall(&v, self.force)?, v)));
+            match res {
+                Ok((size, v)) => {
+                    reclaimed += size;
+                    paris::success!("Removed golang version {} ({})", v, HumanBytes(size));
+                }
+                Err(e) => {
+                    failed += 1;
+                    paris::error!("Failed to remove golang version {}: {}", spec, e);
+                }
+            }
+        }
+        paris::info!("Reclaimed {}", HumanBytes(reclaimed));
+        if failed > 0 {
+            Err(anyhow!("Failed to remove {} version(s)", failed))
+        } else {
+            Ok(())
+        }
+    }
+}
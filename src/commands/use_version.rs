@@ -0,0 +1,55 @@
+use clap::Parser;
+use dialoguer::console::Term;
+
+use crate::consts::RETRIES;
+use crate::envs::EnvManager;
+use crate::project::Pin;
+use crate::utils::{ask_to_install, check_in_path};
+use crate::version_spec::VersionSpec;
+use anyhow::{anyhow, Context, Result};
+
+/// Switch the active golang version
+#[derive(Debug, Clone, Parser)]
+pub(crate) struct Use {
+    /// Installed version to use, e.g. 1.21.5, 1.21, ~1.21, latest or oldstable,
+    /// defaults to the one pinned in .go-version, go.work or go.mod
+    version: Option<VersionSpec>,
+}
+
+impl Use {
+    pub(crate) fn run(self) -> Result<()> {
+        let mut manager = EnvManager::new()?;
+        manager.refresh()?;
+        let spec = match self.version {
+            Some(v) => v,
+            None => {
+                let pin = Pin::from_cwd()?
+                    .context("No version given and no .go-version, go.work or go.mod found")?;
+                paris::info!("Using version {}", pin);
+                pin.spec
+            }
+        };
+        let version = match spec.select(manager.available(), |x| &x.version) {
+            Some(env) => env.version.clone(),
+            None => {
+                let term = Term::stderr();
+                if !term.is_term() || !ask_to_install(&term, &spec)? {
+                    return Err(anyhow!("No installed version matching {}", spec));
+                }
+                let golang = spec.resolve(manager.versions()?)?;
+                let res = golang.download(None, num_cpus::get() as u8, *RETRIES)?;
+                manager.install(&res)?.version
+            }
+        };
+        manager.switch(&version)?;
+        paris::success!(
+            "<b><bright green>Now using golang version {}</></b>",
+            version
+        );
+        let bin_path = manager.current_link().join("bin");
+        if !check_in_path(&bin_path)? {
+            paris::info!("Directory {} not in PATH", bin_path.display());
+        }
+        Ok(())
+    }
+}
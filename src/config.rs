@@ -6,10 +6,11 @@ use anyhow::{Context, Result};
 use clap::Parser;
 use figment::providers::Format;
 use figment::{
-    providers::{Data, Env, Serialized, Toml},
+    providers::{Env, Serialized, Toml},
     Figment,
 };
 use serde::{Deserialize, Serialize};
+use std::cell::OnceCell;
 use std::fs;
 use std::io::Write;
 use std::path::PathBuf;
@@ -18,25 +19,29 @@ use std::path::PathBuf;
 pub(crate) struct App {
     pub(crate) config: Config,
     #[serde(skip)]
-    pub(crate) versions: GoVersions,
+    versions: OnceCell<GoVersions>,
 }
 
 impl App {
     pub(crate) fn new_from_list(config: Config, list_path: PathBuf) -> Result<Self> {
         let versions = GoVersions::new(list_path)?;
-        Ok(Self { config, versions })
+        Ok(Self {
+            config,
+            versions: OnceCell::from(versions),
+        })
     }
     pub(crate) fn new(config: Config) -> Result<Self> {
-        let versions = if let Some(l) = &config.list {
-            l.clone()
-        } else {
-            GoVersions::new(config.list_path.clone()).context("No list available")?
-        };
-        // .as_ref()
-        // .and_then(|x| Some(x.clone()))
-        // .or_else(|| GoVersions::new(config.list_path.clone()).ok())
+        let versions = config.list.clone().map(OnceCell::from).unwrap_or_default();
         Ok(Self { config, versions })
     }
+    /// The release list, only loaded once it's needed so offline commands keep working
+    pub(crate) fn versions(&self) -> Result<&GoVersions> {
+        if let Some(v) = self.versions.get() {
+            return Ok(v);
+        }
+        let list = GoVersions::new(VERSION_LIST.clone()).context("No list available")?;
+        Ok(self.versions.get_or_init(|| list))
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone, Parser)]
@@ -50,6 +55,47 @@ pub(crate) struct Config {
     #[serde(skip)]
     #[clap(skip)]
     pub(crate) list: Option<GoVersions>,
+    /// Hours before the cached version list is refetched
+    #[clap(long)]
+    #[serde(default)]
+    pub(crate) list_ttl: Option<u64>,
+    /// Base URL to download the archives from, e.g. a mirror of go.dev/dl
+    #[clap(long)]
+    #[serde(default)]
+    pub(crate) download_url: Option<String>,
+    /// URL of the release list, defaults to the one under `download_url`
+    #[clap(long)]
+    #[serde(default)]
+    pub(crate) list_url: Option<String>,
+    /// Unpack archives while they download instead of buffering them in memory
+    #[clap(long)]
+    #[serde(default)]
+    pub(crate) stream: Option<bool>,
+    /// Times a failed download is retried
+    #[clap(long)]
+    #[serde(default)]
+    pub(crate) retries: Option<u32>,
+    /// Proxy for every request, HTTPS_PROXY and HTTP_PROXY are used when it's not set
+    #[clap(long)]
+    #[serde(default)]
+    pub(crate) proxy: Option<String>,
+    /// Comma separated hosts that skip the proxy, on top of NO_PROXY
+    #[clap(long)]
+    #[serde(default)]
+    pub(crate) no_proxy: Option<String>,
+    /// PEM bundle of extra root certificates
+    #[clap(long)]
+    #[serde(default)]
+    pub(crate) ca_cert: Option<PathBuf>,
+    /// PEM client certificate, may hold the private key as well
+    #[clap(long)]
+    #[serde(default)]
+    pub(crate) client_cert: Option<PathBuf>,
+    /// PEM private key of the client certificate
+    #[clap(long)]
+    #[serde(default)]
+    pub(crate) client_key: Option<PathBuf>,
+    // a table, so it has to stay after every plain value for toml to write it
     #[clap(skip)]
     pub(crate) current: Option<GoVersion>,
 }
@@ -77,7 +123,7 @@ impl Config {
         let vers = get_local_version(&install_path)?;
         let list_path = config_path
             .parent()
-            .map(|x| x.join("versions.toml"))
+            .map(|x| x.join("versions.json"))
             .unwrap_or_else(|| VERSION_LIST.clone());
         let list = GoVersions::new(list_path.clone()).ok();
         let govers = if let Some(v) = vers {
@@ -95,6 +141,16 @@ impl Config {
             config_path,
             list,
             current: govers,
+            list_ttl: None,
+            download_url: None,
+            list_url: None,
+            stream: None,
+            retries: None,
+            proxy: None,
+            no_proxy: None,
+            ca_cert: None,
+            client_cert: None,
+            client_key: None,
         })
     }
     pub fn save(&self) -> Result<()> {
@@ -104,7 +160,7 @@ impl Config {
                 .create(true)
                 .write(true)
                 .open(&self.list_path)?;
-            file.write_all(toml::to_string_pretty(l)?.as_bytes())?;
+            file.write_all(serde_json::to_string_pretty(l)?.as_bytes())?;
         }
         let mut file = fs::OpenOptions::new()
             .truncate(true)
@@ -117,3 +173,37 @@ impl Config {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saves_config_with_list_ttl() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let path = dir.join("config.toml");
+        fs::write(
+            &path,
+            format!(
+                "install_path = {:?}\nlist_path = {:?}\nconfig_path = {:?}\n",
+                dir,
+                dir.join("versions.json"),
+                path
+            ),
+        )
+        .unwrap();
+        let mut config = Config::from_file(path.clone()).unwrap();
+        config.list_ttl = Some(12);
+        config.current = Some(
+            serde_json::from_value(
+                serde_json::json!({"version": "go1.21.5", "stable": true, "files": []}),
+            )
+            .unwrap(),
+        );
+        config.save().unwrap();
+        let saved = Config::from_file(path).unwrap();
+        assert_eq!(saved.list_ttl, Some(12));
+        assert_eq!(saved.current.unwrap().version, "go1.21.5");
+    }
+}
@@ -1,14 +1,18 @@
-use anyhow::{Context, Result};
+use anyhow::Context;
 use directories::ProjectDirs;
-use serde::{Deserialize, Serialize};
+use figment::providers::{Env, Format, Toml};
+use figment::Figment;
+use serde::de::DeserializeOwned;
 use std::fmt::Display;
 use std::path::PathBuf;
+use std::time::Duration;
 // use crate::error::Result;
-use reqwest::Proxy;
-use semver::Version;
 
 pub const DOWNLOAD_URL: &str = "https://go.dev/dl";
 
+/// Query returning every release as JSON, relative to the download URL
+pub const LIST_QUERY: &str = "?mode=json&include=all";
+
 #[cfg(windows)]
 pub const PATH_SEPERATOR: &str = ";";
 
@@ -17,6 +21,21 @@ pub const PATH_SEPERATOR: &str = ":";
 
 pub const KIND: &str = "archive";
 
+/// Hours the cached version list stays fresh unless `list_ttl` says otherwise
+pub const DEFAULT_LIST_TTL: u64 = 24;
+
+/// Times a failed download is retried unless `retries` says otherwise
+pub const DEFAULT_RETRIES: u32 = 3;
+
+/// Bytes of a streamed archive held in memory while it's unpacked
+pub const STREAM_BUFFER: usize = 1024 * 1024;
+
+/// Env var selecting the golang version when there's no project pin
+pub const VERSION_ENV: &str = "GOM_VERSION";
+
+/// Tools that get a shim dispatching to the version in effect
+pub const SHIMMED: &[&str] = &["go", "gofmt"];
+
 #[cfg(windows)]
 pub const OS: &str = "windows";
 
@@ -26,6 +45,15 @@ pub const OS: &str = "darwin";
 #[cfg(target_os = "linux")]
 pub const OS: &str = "linux";
 
+/// Reads `key` from the config file, overridden by the matching `GOM_` env var
+pub fn setting<T: DeserializeOwned>(key: &str) -> Option<T> {
+    Figment::new()
+        .merge(Toml::file(CONFIG_PATH.as_path()))
+        .merge(Env::prefixed("GOM_"))
+        .extract_inner(key)
+        .ok()
+}
+
 #[cfg(windows)]
 pub fn env_setter<D: Display>(path: D) -> String {
     format!(
@@ -54,83 +82,6 @@ esac"###,
     )
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-struct SysConfig {
-    config_dir: PathBuf,
-    proxies: Option<Vec<String>>,
-    #[serde(skip)]
-    client: manic::Client,
-    versions_list: PathBuf,
-    install_dir: PathBuf,
-    envs_dir: PathBuf,
-    current: Option<Version>,
-}
-
-impl SysConfig {
-    pub fn default() -> Result<Self> {
-        let base_dirs =
-            ProjectDirs::from("rs", "", "go_version_manager").context("Can't get project dirs")?;
-        let config_dirs = base_dirs.config_dir().to_path_buf().join("go_manager");
-        let config_path = config_dirs.join("go_manager.toml");
-        let def_install = base_dirs.data_local_dir().join("envs");
-        if config_path.exists() {
-            Self::from_path(config_path)
-        } else {
-            let (install_dir, version): (PathBuf, Option<Version>) = which::which("go")
-                .ok()
-                .and_then(|x| {
-                    let vers = duct::cmd!(&x, "version")
-                        .read()
-                        .ok()?
-                        .split(' ')
-                        .nth(2)?
-                        .replace("go", "");
-                    Some((
-                        x.parent().map(|x| x.to_path_buf())?,
-                        Version::parse(&vers).ok(),
-                    ))
-                })
-                .and_then(|(x, y)| Some((x.parent().map(|x| x.to_path_buf())?, y)))
-                .unwrap_or((def_install, None));
-            let versions_list = config_dirs.join("versions.toml");
-            let ret = SysConfig {
-                config_dir: config_dirs,
-                proxies: None,
-                client: manic::Client::new(),
-                versions_list,
-                install_dir,
-                envs_dir: base_dirs.data_local_dir().join("go_envs"),
-                current: version,
-            };
-            std::fs::write(config_path, toml::to_string_pretty(&ret)?)?;
-            Ok(ret)
-        }
-    }
-    pub fn from_path(file: PathBuf) -> Result<Self> {
-        let mut ret: SysConfig = toml::from_str(&std::fs::read_to_string(file)?)?;
-        if let Some(p) = &ret.proxies {
-            let client = {
-                let mut b = manic::Client::builder();
-                for i in p {
-                    b = b.proxy(Proxy::all(i)?);
-                }
-                b.build()?
-            };
-            ret.client = client;
-        } else {
-            ret.client = manic::Client::new();
-        }
-        Ok(ret)
-    }
-    pub fn save(&self) -> Result<()> {
-        std::fs::write(
-            self.config_dir.join("go_manager.toml"),
-            toml::to_string_pretty(&self)?,
-        )
-        .context("Failed to save the config")
-    }
-}
-
 lazy_static! {
     pub static ref PROJECT_DIRS: ProjectDirs = ProjectDirs::from("rs", "", "Go Manager").unwrap();
     pub static ref CONFIG_DIR: PathBuf = {
@@ -140,9 +91,20 @@ lazy_static! {
         }
         res
     };
-    pub static ref CLIENT: manic::Client = manic::Client::new();
     pub static ref CONFIG_PATH: PathBuf = CONFIG_DIR.join("config.toml");
     pub static ref VERSION_LIST: PathBuf = CONFIG_DIR.join("versions.json");
+    pub static ref LIST_TTL: Duration =
+        Duration::from_secs(setting("list_ttl").unwrap_or(DEFAULT_LIST_TTL) * 60 * 60);
+    /// Base URL the archives are downloaded from, go.dev unless `download_url` is set
+    pub static ref DOWNLOAD_BASE: String = setting::<String>("download_url")
+        .map(|x| x.trim_end_matches('/').to_string())
+        .unwrap_or_else(|| DOWNLOAD_URL.to_string());
+    /// URL of the release list, defaults to the one next to `DOWNLOAD_BASE`
+    pub static ref LIST_URL: String = setting("list_url")
+        .unwrap_or_else(|| format!("{}/{}", DOWNLOAD_BASE.as_str(), LIST_QUERY));
+    pub static ref RETRIES: u32 = setting("retries").unwrap_or(DEFAULT_RETRIES);
+    /// Whether installs unpack the archive while it downloads instead of buffering it
+    pub static ref STREAM: bool = setting("stream").unwrap_or(false);
     // pub static ref DEFAULT_INSTALL: PathBuf = {
     //     if cfg!(windows) {
     //         PathBuf::from("C:\\Go")
@@ -161,6 +123,8 @@ lazy_static! {
                                                         .map(|x| x.to_path_buf())
     }).ok();
     pub static ref ENVS_DIR: PathBuf = PROJECT_DIRS.data_local_dir().join("envs");
+    pub static ref SHIMS_DIR: PathBuf = PROJECT_DIRS.data_local_dir().join("shims");
+    pub static ref CACHE_DIR: PathBuf = PROJECT_DIRS.data_local_dir().join("cache");
     pub static ref ARCH: String = {
         match std::env::consts::ARCH {
             "x86_64" => "amd64".to_string(),
@@ -1,49 +1,486 @@
 // use crate::{Error, Result};
 use anyhow::Context;
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use flate2::bufread::GzDecoder;
 use std::io::{BufRead, Read, Seek};
-use std::path::Path;
+use std::path::{Component, Path, PathBuf};
+use tar::{Archive, EntryType};
 use tracing::instrument;
+use zip::ZipArchive;
 
-#[cfg(unix)]
-use flate2::bufread::GzDecoder;
-#[cfg(unix)]
-use tar::Archive;
+/// Every entry of a golang archive lives under this dir
+const PREFIX: &str = "go";
+
+/// Archive formats golang releases are shipped in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Format {
+    TarGz,
+    Zip,
+}
+
+impl Format {
+    /// Detects the format from the first bytes of the archive
+    pub(crate) fn from_magic(head: &[u8]) -> Option<Self> {
+        if head.starts_with(&[0x1f, 0x8b]) {
+            Some(Self::TarGz)
+        } else if head.starts_with(b"PK\x03\x04") || head.starts_with(b"PK\x05\x06") {
+            Some(Self::Zip)
+        } else {
+            None
+        }
+    }
+    /// Detects the format from the archive's file name
+    pub(crate) fn from_name(name: &Path) -> Option<Self> {
+        let name = name.file_name()?.to_str()?.to_lowercase();
+        if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(Self::TarGz)
+        } else if name.ends_with(".zip") {
+            Some(Self::Zip)
+        } else {
+            None
+        }
+    }
+}
+
+enum Decompressor<R: Read + Seek + BufRead> {
+    TarGz(Archive<GzDecoder<R>>),
+    Zip(ZipArchive<R>),
+}
 
 pub struct ToDecompress<R>
 where
     R: Read + Seek + BufRead,
 {
-    #[cfg(windows)]
-    decompressor: zip::ZipArchive<R>,
-    #[cfg(unix)]
-    decompressor: Archive<GzDecoder<R>>,
+    decompressor: Decompressor<R>,
 }
 
 impl<R: Read + Seek + BufRead> ToDecompress<R> {
-    #[cfg(windows)]
-    pub(crate) fn new(inner: R) -> Result<Self> {
-        Ok(Self {
-            decompressor: zip::ZipArchive::new(inner)?,
-        })
-    }
-    #[cfg(unix)]
-    pub(crate) fn new(inner: R) -> Result<Self> {
-        let dec = Archive::new(GzDecoder::new(inner));
-        Ok(Self { decompressor: dec })
-    }
-    #[cfg(windows)]
-    #[instrument(skip(self))]
-    pub(crate) fn extract(&mut self, path: &Path) -> Result<()> {
-        self.decompressor
-            .extract(path.parent().context("No parent")?)
-            .context("Unpacking error")
+    /// Opens the archive, the format is taken from its magic bytes
+    /// and falls back to the extension of `name`
+    pub(crate) fn new(mut inner: R, name: Option<&Path>) -> Result<Self> {
+        let head = inner.fill_buf().context("Failed to read the archive")?;
+        let by_name = name.and_then(Format::from_name);
+        let format = match Format::from_magic(head) {
+            Some(f) => {
+                if by_name.is_some_and(|x| x != f) {
+                    paris::warn!(
+                        "{} looks like a {:?} archive, unpacking it as one",
+                        name.map(|x| x.display().to_string()).unwrap_or_default(),
+                        f
+                    );
+                }
+                f
+            }
+            None => by_name.context("Unknown archive format, expected .tar.gz or .zip")?,
+        };
+        Self::with_format(inner, format)
+    }
+    pub(crate) fn with_format(inner: R, format: Format) -> Result<Self> {
+        let decompressor = match format {
+            Format::TarGz => Decompressor::TarGz(Archive::new(GzDecoder::new(inner))),
+            Format::Zip => {
+                Decompressor::Zip(ZipArchive::new(inner).context("Invalid zip archive")?)
+            }
+        };
+        Ok(Self { decompressor })
+    }
+    pub(crate) fn format(&self) -> Format {
+        match self.decompressor {
+            Decompressor::TarGz(_) => Format::TarGz,
+            Decompressor::Zip(_) => Format::Zip,
+        }
     }
-    #[cfg(unix)]
+    /// Unpacks the archive next to `path`, refusing entries outside of `go/`
+    /// and symlinks or hard links pointing out of it
     #[instrument(skip(self))]
     pub(crate) fn extract(&mut self, path: &Path) -> Result<()> {
-        self.decompressor
-            .unpack(path.parent().context("No parent")?)
-            .context("Unpacking error")
+        let root = path.parent().context("No parent")?;
+        match &mut self.decompressor {
+            Decompressor::TarGz(x) => extract_tar(x, root),
+            Decompressor::Zip(x) => extract_zip(x, root),
+        }
+    }
+}
+
+/// Unpacks a tar.gz read from a stream next to `path` and reads the stream to its end,
+/// zip archives can't be streamed as their index is at the end
+#[instrument(skip(inner))]
+pub(crate) fn extract_tar_gz<R: BufRead>(inner: R, path: &Path) -> Result<()> {
+    let root = path.parent().context("No parent")?;
+    let mut archive = Archive::new(GzDecoder::new(inner));
+    extract_tar(&mut archive, root)?;
+    // the rest of the gzip stream, so its trailer gets checked, then anything after it
+    let mut gz = archive.into_inner();
+    std::io::copy(&mut gz, &mut std::io::sink()).context("Unpacking error")?;
+    std::io::copy(gz.get_mut(), &mut std::io::sink()).context("Unpacking error")?;
+    Ok(())
+}
+
+fn extract_zip<R: Read + Seek>(archive: &mut ZipArchive<R>, root: &Path) -> Result<()> {
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i).context("Unpacking error")?;
+        let name = file.name().to_string();
+        let rel = file
+            .enclosed_name()
+            .map(|x| x.to_path_buf())
+            .with_context(|| format!("Entry {} escapes the target dir", name))?;
+        let rel = entry_path(&rel).with_context(|| format!("Bad entry {}", name))?;
+        let is_link = file.unix_mode().is_some_and(|m| m & 0o170000 == 0o120000);
+        if is_link {
+            return Err(anyhow!(
+                "Entry {} is a symlink, they're not supported in zip archives",
+                name
+            ));
+        }
+        let out = root.join(&rel);
+        if file.is_dir() {
+            std::fs::create_dir_all(&out)?;
+            continue;
+        }
+        if let Some(p) = out.parent() {
+            std::fs::create_dir_all(p)?;
+        }
+        let mut target = std::fs::File::create(&out)
+            .with_context(|| format!("Failed to create {}", out.display()))?;
+        std::io::copy(&mut file, &mut target)
+            .with_context(|| format!("Failed to unpack {}", name))?;
+        #[cfg(unix)]
+        if let Some(mode) = file.unix_mode() {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&out, std::fs::Permissions::from_mode(mode & 0o777))?;
+        }
+    }
+    Ok(())
+}
+
+fn extract_tar<R: Read>(archive: &mut Archive<R>, root: &Path) -> Result<()> {
+    let mut links = Vec::new();
+    for entry in archive.entries().context("Unpacking error")? {
+        let mut entry = entry.context("Unpacking error")?;
+        let raw = entry.path().context("Unpacking error")?.into_owned();
+        let name = raw.display().to_string();
+        let rel = entry_path(&raw).with_context(|| format!("Bad entry {}", name))?;
+        match entry.header().entry_type() {
+            EntryType::Regular | EntryType::Continuous | EntryType::Directory => {}
+            EntryType::Symlink => {
+                let target = entry
+                    .link_name()?
+                    .with_context(|| format!("Symlink {} has no target", name))?;
+                check_symlink(&rel, &target).with_context(|| format!("Bad symlink {}", name))?;
+                links.push(rel);
+            }
+            EntryType::Link => {
+                let target = entry
+                    .link_name()?
+                    .with_context(|| format!("Hard link {} has no target", name))?;
+                let target =
+                    entry_path(&target).with_context(|| format!("Bad hard link {}", name))?;
+                check_on_disk(root, &target).with_context(|| format!("Bad hard link {}", name))?;
+            }
+            // pax and gnu extension headers are consumed by the iterator
+            // so anything else is a device, fifo or similar
+            other => return Err(anyhow!("Entry {} has unsupported type {:?}", name, other)),
+        }
+        entry
+            .unpack_in(root)
+            .with_context(|| format!("Failed to unpack {}", name))?;
+    }
+    for rel in links {
+        check_on_disk(root, &rel).with_context(|| format!("Bad symlink {}", rel.display()))?;
+    }
+    Ok(())
+}
+
+/// The entry path with `.` components dropped, it has to be relative and inside `go/`
+fn entry_path(path: &Path) -> Result<PathBuf> {
+    let mut ret = PathBuf::new();
+    for c in path.components() {
+        match c {
+            Component::Normal(x) => ret.push(x),
+            Component::CurDir => {}
+            Component::ParentDir => return Err(anyhow!("it escapes the target dir")),
+            Component::RootDir | Component::Prefix(_) => {
+                return Err(anyhow!("it's an absolute path"))
+            }
+        }
+    }
+    if !ret.starts_with(PREFIX) {
+        return Err(anyhow!("it's outside of {}/", PREFIX));
+    }
+    Ok(ret)
+}
+
+/// Checks that the symlink at `entry` pointing to `target` resolves inside `go/`
+fn check_symlink(entry: &Path, target: &Path) -> Result<()> {
+    let mut resolved: Vec<Component> = entry
+        .parent()
+        .map(|x| x.components().collect())
+        .unwrap_or_default();
+    for c in target.components() {
+        match c {
+            Component::Normal(_) => resolved.push(c),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                resolved
+                    .pop()
+                    .context("its target escapes the target dir")?;
+            }
+            Component::RootDir | Component::Prefix(_) => {
+                return Err(anyhow!("its target is an absolute path"))
+            }
+        }
+    }
+    if !resolved.iter().collect::<PathBuf>().starts_with(PREFIX) {
+        return Err(anyhow!("its target is outside of {}/", PREFIX));
+    }
+    Ok(())
+}
+
+/// Checks that `rel` resolves inside `go/` once every link on its way is followed,
+/// links can be chained through each other, e.g. `go/d -> .` and `go/e -> d/..`,
+/// which `check_symlink` can't tell from the names alone
+fn check_on_disk(root: &Path, rel: &Path) -> Result<()> {
+    let go = root
+        .join(PREFIX)
+        .canonicalize()
+        .context("Unpacking error")?;
+    let resolved = root
+        .join(rel)
+        .canonicalize()
+        .context("its target doesn't exist")?;
+    if !resolved.starts_with(go) {
+        return Err(anyhow!("its target is outside of {}/", PREFIX));
+    }
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::{BufReader, Cursor, Write};
+    use tar::{Builder, Header};
+    use tempfile::TempDir;
+    use zip::write::{FileOptions, ZipWriter};
+
+    /// Header with `path` written straight into the name field so `..` and
+    /// absolute paths get through, `tar::Builder` refuses those
+    fn header(path: &str, kind: EntryType, link: Option<&str>, size: u64) -> Header {
+        let mut h = Header::new_old();
+        h.as_old_mut().name[..path.len()].copy_from_slice(path.as_bytes());
+        if let Some(l) = link {
+            h.as_old_mut().linkname[..l.len()].copy_from_slice(l.as_bytes());
+        }
+        h.set_entry_type(kind);
+        h.set_size(size);
+        h.set_mode(0o755);
+        h.set_cksum();
+        h
+    }
+
+    fn archive(entries: &[(&str, EntryType, Option<&str>)]) -> Vec<u8> {
+        let mut b = Builder::new(GzEncoder::new(Vec::new(), Compression::fast()));
+        for (path, kind, link) in entries {
+            let data: &[u8] = if *kind == EntryType::Regular {
+                b"data"
+            } else {
+                b""
+            };
+            b.append(&header(path, *kind, *link, data.len() as u64), data)
+                .unwrap();
+        }
+        b.into_inner().unwrap().finish().unwrap()
+    }
+
+    fn zip_archive(entries: &[&str]) -> Vec<u8> {
+        let mut w = ZipWriter::new(Cursor::new(Vec::new()));
+        for path in entries {
+            if path.ends_with('/') {
+                w.add_directory(*path, FileOptions::default()).unwrap();
+            } else {
+                let opts = FileOptions::default().unix_permissions(0o755);
+                w.start_file(*path, opts).unwrap();
+                w.write_all(b"data").unwrap();
+            }
+        }
+        w.finish().unwrap().into_inner()
+    }
+
+    /// Temp dir with an empty `root` dir to unpack into
+    fn scratch() -> TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("root")).unwrap();
+        dir
+    }
+
+    fn extract(buf: Vec<u8>) -> (TempDir, Result<()>) {
+        let dir = scratch();
+        let root = dir.path().join("root");
+        let res = ToDecompress::new(BufReader::new(Cursor::new(buf)), None)
+            .unwrap()
+            .extract(&root.join("go"));
+        (dir, res)
+    }
+
+    fn rejects(entries: &[(&str, EntryType, Option<&str>)], offending: &str) {
+        let (dir, res) = extract(archive(entries));
+        let err = format!("{:#}", res.unwrap_err());
+        assert!(err.contains(offending), "{}", err);
+        assert!(!dir.path().join("evil").exists());
+    }
+
+    #[test]
+    fn unpacks_valid_archive() {
+        let (dir, res) = extract(archive(&[
+            ("go/", EntryType::Directory, None),
+            ("go/bin/go", EntryType::Regular, None),
+            ("./go/VERSION", EntryType::Regular, None),
+            ("go/bin/gofmt", EntryType::Symlink, Some("go")),
+            ("go/misc/lib", EntryType::Symlink, Some("../bin")),
+            ("go/bin/go2", EntryType::Link, Some("go/bin/go")),
+        ]));
+        res.unwrap();
+        assert!(dir.path().join("root/go/bin/go").is_file());
+        assert!(dir.path().join("root/go/VERSION").is_file());
+        assert!(dir.path().join("root/go/misc/lib/go2").is_file());
+    }
+
+    #[test]
+    fn rejects_parent_dir_entries() {
+        rejects(
+            &[("go/../../evil", EntryType::Regular, None)],
+            "go/../../evil",
+        );
+    }
+
+    #[test]
+    fn rejects_absolute_entries() {
+        rejects(&[("/tmp/evil", EntryType::Regular, None)], "/tmp/evil");
+    }
+
+    #[test]
+    fn rejects_entries_outside_prefix() {
+        rejects(&[("evil/go", EntryType::Regular, None)], "evil/go");
+    }
+
+    #[test]
+    fn rejects_escaping_symlinks() {
+        rejects(
+            &[("go/evil", EntryType::Symlink, Some("../../evil"))],
+            "go/evil",
+        );
+        rejects(&[("go/evil", EntryType::Symlink, Some("/etc"))], "go/evil");
+        rejects(&[("go/evil", EntryType::Symlink, Some(".."))], "go/evil");
+    }
+
+    #[test]
+    fn rejects_chained_symlinks() {
+        rejects(
+            &[
+                ("go/d", EntryType::Symlink, Some(".")),
+                ("go/d2", EntryType::Symlink, Some("d/..")),
+                ("go/e", EntryType::Symlink, Some("d2/..")),
+            ],
+            "go/d2",
+        );
+    }
+
+    #[test]
+    fn rejects_escaping_hard_links() {
+        rejects(&[("go/evil", EntryType::Link, Some("../evil"))], "go/evil");
+        rejects(
+            &[("go/evil", EntryType::Link, Some("/etc/passwd"))],
+            "go/evil",
+        );
+    }
+
+    #[test]
+    fn rejects_device_entries() {
+        rejects(&[("go/evil", EntryType::Char, None)], "go/evil");
+    }
+
+    #[test]
+    fn detects_formats() {
+        let tgz = archive(&[("go/VERSION", EntryType::Regular, None)]);
+        let zip = zip_archive(&["go/VERSION"]);
+        assert_eq!(Format::from_magic(&tgz), Some(Format::TarGz));
+        assert_eq!(Format::from_magic(&zip), Some(Format::Zip));
+        assert_eq!(Format::from_magic(b"garbage"), None);
+        assert_eq!(
+            Format::from_name(Path::new("/tmp/go1.21.5.linux-amd64.tar.gz")),
+            Some(Format::TarGz)
+        );
+        assert_eq!(
+            Format::from_name(Path::new("go1.21.5.windows-amd64.ZIP")),
+            Some(Format::Zip)
+        );
+        assert_eq!(
+            Format::from_name(Path::new("go1.21.5.windows-amd64.msi")),
+            None
+        );
+        // the magic bytes win over a misleading name
+        let named_zip = Path::new("go.zip");
+        let dec = ToDecompress::new(BufReader::new(Cursor::new(tgz)), Some(named_zip)).unwrap();
+        assert_eq!(dec.format(), Format::TarGz);
+        let err = ToDecompress::new(BufReader::new(Cursor::new(b"garbage".to_vec())), None)
+            .err()
+            .unwrap();
+        assert!(
+            err.to_string().contains("Unknown archive format"),
+            "{}",
+            err
+        );
+        // an empty file falls back to the name
+        let err = ToDecompress::new(BufReader::new(Cursor::new(Vec::new())), Some(named_zip))
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("Invalid zip"), "{}", err);
+    }
+
+    #[test]
+    fn unpacks_valid_zip() {
+        let (dir, res) = extract(zip_archive(&["go/", "go/bin/go.exe", "go/VERSION"]));
+        res.unwrap();
+        assert!(dir.path().join("root/go/bin/go.exe").is_file());
+        assert!(dir.path().join("root/go/VERSION").is_file());
+        let mode = std::os::unix::fs::PermissionsExt::mode(
+            &dir.path()
+                .join("root/go/bin/go.exe")
+                .metadata()
+                .unwrap()
+                .permissions(),
+        );
+        assert_eq!(mode & 0o777, 0o755);
+    }
+
+    #[test]
+    fn rejects_bad_zip_entries() {
+        for name in ["go/../../evil", "/evil", "evil/go"] {
+            let (dir, res) = extract(zip_archive(&[name]));
+            let err = format!("{:#}", res.unwrap_err());
+            assert!(err.contains(name), "{}", err);
+            assert!(!dir.path().join("evil").exists());
+        }
+    }
+
+    #[test]
+    fn streams_tar_gz_to_the_end() {
+        use crate::cache::Sha256Reader;
+        use sha2::{Digest, Sha256};
+        let buf = archive(&[
+            ("go/", EntryType::Directory, None),
+            ("go/bin/go", EntryType::Regular, None),
+        ]);
+        let expected = format!("{:x}", Sha256::digest(&buf));
+        let dir = scratch();
+        let mut reader = Sha256Reader::new(Cursor::new(buf));
+        extract_tar_gz(
+            BufReader::with_capacity(16, &mut reader),
+            &dir.path().join("root/go"),
+        )
+        .unwrap();
+        assert!(dir.path().join("root/go/bin/go").is_file());
+        assert_eq!(reader.finish(), expected);
     }
 }
@@ -1,12 +1,19 @@
-use crate::config::{App, Config};
-use crate::consts::{env_setter, CONFIG_PATH, ENVS_DIR};
-use anyhow::{Context, Result};
+use crate::config::Config;
+use crate::consts::{env_setter, CONFIG_PATH, ENVS_DIR, VERSION_ENV, VERSION_LIST};
+use crate::goversion::{parse_go_version, Downloaded, GoVersions};
+use crate::project::Pin;
+use crate::utils::dir_size;
+use crate::version_spec::VersionSpec;
+use anyhow::{anyhow, Context, Result};
 use rayon::prelude::*;
 use semver::Version;
 use serde::{Deserialize, Serialize};
+use std::cell::OnceCell;
+use std::ffi::OsString;
 use std::fs;
 use std::path::Path;
 use std::path::PathBuf;
+use tracing::instrument;
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct InstalledEnv {
@@ -22,14 +29,17 @@ impl InstalledEnv {
             return toml::from_str(&fs::read_to_string(potential_env_file)?)
                 .context("Failed to parse env file");
         }
-        let version = Version::parse(
-            &duct::cmd!(&go_path, "version")
+        // run from the env itself with toolchain switching off, so a go.mod around
+        // the caller's cwd can't make `go` hand over to another release
+        let version = parse_go_version(
+            duct::cmd!(&go_path, "version")
+                .env("GOTOOLCHAIN", "local")
+                .dir(path)
                 .read()
                 .context("Can't get go version")?
                 .split(' ')
                 .nth(2)
-                .context("Can't get go version")?
-                .replace("go", ""),
+                .context("Can't get go version")?,
         )
         .context("Can't parse version from go version")?;
         Ok(InstalledEnv {
@@ -37,13 +47,52 @@ impl InstalledEnv {
             path: path.to_path_buf(),
         })
     }
+    pub fn bin_dir(&self) -> PathBuf {
+        self.path.join("bin")
+    }
+    /// The current `PATH` with this env's bin dir in front
+    pub fn path_var(&self) -> Result<OsString> {
+        let rest = std::env::var_os("PATH")
+            .map(|x| std::env::split_paths(&x).collect::<Vec<_>>())
+            .unwrap_or_default();
+        std::env::join_paths(std::iter::once(self.bin_dir()).chain(rest))
+            .context("Failed to build PATH")
+    }
+    /// `PATH`, `GOROOT` and `GOTOOLCHAIN` as a child process of this env should see them
+    pub fn env_vars(&self) -> Result<[(&'static str, OsString); 3]> {
+        Ok([
+            ("PATH", self.path_var()?),
+            ("GOROOT", self.path.clone().into_os_string()),
+            ("GOTOOLCHAIN", OsString::from("local")),
+        ])
+    }
+    /// Command running `program` with the env vars of this env
+    pub fn command<I>(&self, program: &str, args: I) -> Result<duct::Expression>
+    where
+        I: IntoIterator,
+        I::Item: Into<OsString>,
+    {
+        let vars = self.env_vars()?;
+        let program = which::which_in(program, Some(&vars[0].1), std::env::current_dir()?)
+            .with_context(|| format!("Can't find {}", program))?;
+        Ok(vars
+            .into_iter()
+            .fold(duct::cmd(program, args), |cmd, (k, v)| cmd.env(k, v)))
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct EnvManager {
-    app: App,
     env_dir: PathBuf,
-    current: InstalledEnv,
+    /// Loaded once it's needed, the settings stay in config.toml which only `config` writes
+    #[serde(skip)]
+    versions: OnceCell<GoVersions>,
+    current: Option<InstalledEnv>,
+    /// The env `current` pointed at before the last switch, restored by `rollback`
+    #[serde(default)]
+    previous: Option<InstalledEnv>,
+    // toml can't write an empty array once a table came before it
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     available: Vec<InstalledEnv>,
 }
 
@@ -53,26 +102,18 @@ impl EnvManager {
         match from_list(env_dir.as_path()) {
             Some(x) => Ok(x),
             None => {
-                let current = InstalledEnv::new(&env_dir.join("current"))?;
-                // let available = vec![current.clone()];
-                let available = fs::read_dir(&env_dir)
-                    .context("Can't read env dir")?
-                    .par_bridge()
-                    .filter_map(|x| {
-                        let opt = x.ok()?;
-                        if opt.file_name().to_str()?.contains("current") {
-                            None
-                        } else {
-                            Some(InstalledEnv::new(opt.path().as_path()).ok()?)
-                        }
-                    })
-                    .collect();
-                let config = Config::new(env_dir.clone(), CONFIG_PATH.clone())?;
-                let app = config.into_app()?;
+                let current = fs::canonicalize(env_dir.join("current"))
+                    .ok()
+                    .and_then(|x| InstalledEnv::new(&x).ok());
+                let available = scan_envs(&env_dir)?;
+                if !CONFIG_PATH.exists() {
+                    Config::new(env_dir.clone(), CONFIG_PATH.clone())?.save()?;
+                }
                 let ret = EnvManager {
-                    app,
                     env_dir,
+                    versions: OnceCell::new(),
                     current,
+                    previous: None,
                     available,
                 };
                 ret.save()?;
@@ -80,6 +121,167 @@ impl EnvManager {
             }
         }
     }
+    pub fn current(&self) -> Option<&InstalledEnv> {
+        self.current.as_ref()
+    }
+    pub fn available(&self) -> &[InstalledEnv] {
+        &self.available
+    }
+    /// The env in effect for the working directory, a project pin wins over `GOM_VERSION`
+    /// which wins over the global current, returned along with where the choice came from
+    pub fn active(&mut self) -> Result<(InstalledEnv, String)> {
+        let (spec, source): (VersionSpec, String) = if let Some(pin) = Pin::from_cwd()? {
+            (pin.spec, format!("set by {}", pin.source.display()))
+        } else if let Ok(v) = std::env::var(VERSION_ENV) {
+            (v.parse()?, format!("set by {}", VERSION_ENV))
+        } else {
+            return self
+                .current
+                .clone()
+                .map(|x| (x, "global".to_string()))
+                .context("No golang version in use");
+        };
+        if spec.select(&self.available, |x| &x.version).is_none() {
+            self.refresh()?;
+        }
+        let env = spec
+            .select(&self.available, |x| &x.version)
+            .cloned()
+            .with_context(|| {
+                format!(
+                    "Version {} ({}) is not installed, run install to get it",
+                    spec, source
+                )
+            })?;
+        Ok((env, source))
+    }
+    pub fn find(&self, version: &Version) -> Option<&InstalledEnv> {
+        self.available.iter().find(|x| &x.version == version)
+    }
+    /// Rescans the env dir, picking up envs installed since `envs.toml` was written
+    pub fn refresh(&mut self) -> Result<()> {
+        self.available = scan_envs(&self.env_dir)?;
+        Ok(())
+    }
+    /// Points the `current` link at the chosen version and persists the change
+    #[instrument(err, skip(self))]
+    pub fn switch(&mut self, version: &Version) -> Result<()> {
+        if self.find(version).is_none() {
+            self.refresh()?;
+        }
+        let env = self
+            .find(version)
+            .cloned()
+            .with_context(|| format!("Version {} is not installed", version))?;
+        swap_link(&env.path, &self.current_link())?;
+        if let Some(old) = self.current.take().filter(|x| x.path != env.path) {
+            self.previous = Some(old);
+        }
+        self.current = Some(env);
+        self.save()
+    }
+    pub fn previous(&self) -> Option<&InstalledEnv> {
+        self.previous.as_ref()
+    }
+    /// Switches back to the version in use before the last switch
+    #[instrument(err, skip(self))]
+    pub fn rollback(&mut self) -> Result<InstalledEnv> {
+        let prev = self
+            .previous
+            .clone()
+            .context("There's no previous version to roll back to")?;
+        self.switch(&prev.version)?;
+        Ok(prev)
+    }
+    /// Unpacks a downloaded version into a staging dir next to the envs, checks that its
+    /// `go version` matches and only then renames it into place as `go<version>`
+    #[instrument(err, skip(self, dl))]
+    pub fn install(&mut self, dl: &Downloaded) -> Result<InstalledEnv> {
+        let version = dl.version().parsed.clone();
+        let target = self.env_dir.join(format!("go{}", version));
+        if target.exists() {
+            return Err(anyhow!(
+                "{} already exists, version {} is already installed",
+                target.display(),
+                version
+            ));
+        }
+        let staging = self.env_dir.join(format!(".go{}.partial", version));
+        if staging.exists() {
+            fs::remove_dir_all(&staging).context("Failed to remove an unfinished install")?;
+        }
+        fs::create_dir_all(&staging)?;
+        let res = stage(dl, &staging.join("go"), &version, &target);
+        if let Err(e) = fs::remove_dir_all(&staging) {
+            paris::warn!("Failed to clean up {}: {}", staging.display(), e);
+        }
+        let env = res?;
+        self.available.push(env.clone());
+        self.available
+            .sort_unstable_by(|a, b| a.version.cmp(&b.version));
+        self.save()?;
+        Ok(env)
+    }
+    /// Removes an installed version and returns the reclaimed space in bytes,
+    /// the current version is only removed when `force` is set
+    #[instrument(err, skip(self))]
+    pub fn uninstall(&mut self, version: &Version, force: bool) -> Result<u64> {
+        if self.find(version).is_none() {
+            self.refresh()?;
+        }
+        let env = self
+            .find(version)
+            .cloned()
+            .with_context(|| format!("Version {} is not installed", version))?;
+        let is_current = self.current.as_ref().map(|x| &x.path) == Some(&env.path);
+        if is_current && !force {
+            return Err(anyhow!(
+                "Version {} is the current version, use --force to remove it anyway",
+                version
+            ));
+        }
+        let size = dir_size(&env.path)?;
+        fs::remove_dir_all(&env.path)
+            .with_context(|| format!("Failed to remove {}", env.path.display()))?;
+        self.available.retain(|x| x.path != env.path);
+        if self.previous.as_ref().map(|x| &x.path) == Some(&env.path) {
+            self.previous = None;
+        }
+        if is_current {
+            remove_link(&self.current_link())?;
+            self.current = None;
+        }
+        self.save()?;
+        Ok(size)
+    }
+    /// The release list, only loaded once it's needed so offline commands keep working
+    pub fn versions(&self) -> Result<&GoVersions> {
+        if let Some(v) = self.versions.get() {
+            return Ok(v);
+        }
+        let list = GoVersions::new(VERSION_LIST.clone()).context("No list available")?;
+        Ok(self.versions.get_or_init(|| list))
+    }
+    pub fn env_dir(&self) -> &Path {
+        &self.env_dir
+    }
+    /// Whether `p` lies inside one of the versioned envs, the `current` link doesn't count
+    pub fn is_managed(&self, p: &Path) -> bool {
+        p.starts_with(&self.env_dir) && !p.starts_with(self.current_link())
+    }
+    /// The current `PATH` without the bin dirs of versioned envs
+    pub fn unmanaged_path(&self) -> Vec<PathBuf> {
+        std::env::var_os("PATH")
+            .map(|x| {
+                std::env::split_paths(&x)
+                    .filter(|p| !self.is_managed(p))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+    pub fn current_link(&self) -> PathBuf {
+        self.env_dir.join("current")
+    }
     pub fn save(&self) -> Result<()> {
         fs::write(
             self.env_dir.join("envs.toml"),
@@ -87,16 +289,114 @@ impl EnvManager {
         )?;
         fs::write(
             self.env_dir.join(".go.env"),
-            env_setter(self.current.path.join("bin").display()),
+            env_setter(self.current_link().join("bin").display()),
         )?;
-        self.app.config.save()?;
         Ok(())
     }
 }
 
+/// Unpacks `dl` to `unpacked`, validates it and moves it to `target`
+fn stage(
+    dl: &Downloaded,
+    unpacked: &Path,
+    version: &Version,
+    target: &Path,
+) -> Result<InstalledEnv> {
+    dl.unpack(unpacked)?;
+    let found = InstalledEnv::new(unpacked)
+        .context("The unpacked toolchain doesn't work")?
+        .version;
+    if &found != version {
+        return Err(anyhow!(
+            "The unpacked toolchain reports version {} instead of {}",
+            found,
+            version
+        ));
+    }
+    let env = InstalledEnv {
+        version: version.clone(),
+        path: target.to_path_buf(),
+    };
+    fs::write(
+        unpacked.join(".go_version.env"),
+        toml::to_string_pretty(&env)?,
+    )?;
+    fs::rename(unpacked, target)
+        .with_context(|| format!("Failed to move the toolchain to {}", target.display()))?;
+    Ok(env)
+}
+
+fn scan_envs(env_dir: &Path) -> Result<Vec<InstalledEnv>> {
+    let mut available: Vec<InstalledEnv> = fs::read_dir(env_dir)
+        .context("Can't read env dir")?
+        .par_bridge()
+        .filter_map(|x| {
+            let opt = x.ok()?;
+            let name = opt.file_name();
+            let name = name.to_str()?;
+            // dot dirs are staging dirs of installs that never finished
+            if name.contains("current") || name.starts_with('.') {
+                None
+            } else {
+                Some(InstalledEnv::new(opt.path().as_path()).ok()?)
+            }
+        })
+        .collect();
+    available.sort_unstable_by(|a, b| a.version.cmp(&b.version));
+    Ok(available)
+}
+
+/// Replaces `link` with a symlink to `target`, on unix the new link is
+/// created beside the old one and renamed over it so there's never a moment without one
+#[cfg(unix)]
+fn swap_link(target: &Path, link: &Path) -> Result<()> {
+    if link.exists() && !link.symlink_metadata()?.file_type().is_symlink() {
+        return Err(anyhow!(
+            "{} is not a symlink, refusing to replace it",
+            link.display()
+        ));
+    }
+    let tmp = link.with_file_name(".current.tmp");
+    if tmp.symlink_metadata().is_ok() {
+        fs::remove_file(&tmp)?;
+    }
+    std::os::unix::fs::symlink(target, &tmp).context("Failed to create the symlink")?;
+    fs::rename(&tmp, link).context("Failed to replace the current symlink")
+}
+
+#[cfg(unix)]
+fn remove_link(link: &Path) -> Result<()> {
+    if link.symlink_metadata().is_ok() {
+        fs::remove_file(link).context("Failed to remove the current symlink")?;
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn remove_link(link: &Path) -> Result<()> {
+    if link.symlink_metadata().is_ok() {
+        fs::remove_dir(link).context("Failed to remove the current symlink")?;
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn swap_link(target: &Path, link: &Path) -> Result<()> {
+    if let Ok(meta) = link.symlink_metadata() {
+        if !meta.file_type().is_symlink() {
+            return Err(anyhow!(
+                "{} is not a symlink, refusing to replace it",
+                link.display()
+            ));
+        }
+        fs::remove_dir(link).context("Failed to remove the current symlink")?;
+    }
+    std::os::windows::fs::symlink_dir(target, link).context("Failed to create the symlink")
+}
+
 fn env_dir() -> Result<PathBuf> {
     if !ENVS_DIR.exists() {
-        fs::create_dir(ENVS_DIR.as_path()).context("Can't create env dir")?;
+        fs::create_dir_all(ENVS_DIR.as_path()).context("Can't create env dir")?;
     }
     Ok(ENVS_DIR.to_path_buf())
 }
@@ -124,3 +424,46 @@ fn from_list(path: &Path) -> Option<EnvManager> {
 //     std::env::set_var("PATH", path);
 //     Ok(())
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manager(env_dir: &Path) -> EnvManager {
+        EnvManager {
+            env_dir: env_dir.to_path_buf(),
+            versions: OnceCell::new(),
+            current: Some(InstalledEnv {
+                version: Version::new(1, 21, 5),
+                path: env_dir.join("go1.21.5"),
+            }),
+            previous: None,
+            available: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn saves_manager_without_envs() {
+        let tmp = tempfile::tempdir().unwrap();
+        manager(tmp.path()).save().unwrap();
+        let saved: toml::Value =
+            toml::from_str(&fs::read_to_string(tmp.path().join("envs.toml")).unwrap()).unwrap();
+        assert!(saved.get("available").is_none());
+        assert!(saved.get("app").is_none());
+        assert_eq!(saved["current"]["version"].as_str(), Some("1.21.5"));
+    }
+
+    #[test]
+    fn keeps_hand_edited_config() {
+        let tmp = tempfile::tempdir().unwrap();
+        let config = tmp.path().join("config.toml");
+        let hand_edited =
+            "download_url = \"http://mirror/dl\"\nproxy = \"http://corp-proxy:3128\"\n";
+        fs::write(&config, hand_edited).unwrap();
+        let manager = manager(tmp.path());
+        manager.save().unwrap();
+        let reloaded = from_list(tmp.path()).unwrap();
+        reloaded.save().unwrap();
+        assert_eq!(fs::read_to_string(&config).unwrap(), hand_edited);
+    }
+}
@@ -1,24 +1,30 @@
-use crate::consts::{ARCH, CLIENT, DOWNLOAD_URL, VERSION_LIST};
-use crate::decompressor::ToDecompress;
+use crate::cache::{sha256_file, ArchiveCache, Sha256Reader, TeeReader};
+use crate::consts::{ARCH, DOWNLOAD_BASE, LIST_TTL, LIST_URL, STREAM, STREAM_BUFFER, VERSION_LIST};
+use crate::decompressor::{extract_tar_gz, Format, ToDecompress};
+use crate::http::client;
+use crate::transfer::{download, probe, progress_bar, RangeReader, Retry};
 use crate::utils::get_local_version;
-use anyhow::Context;
+use anyhow::{anyhow, Context};
 
 use anyhow::Result;
 
-use manic::Downloader;
+use itertools::Itertools;
 use rayon::prelude::*;
-use semver::Version;
+use semver::{Prerelease, Version};
 use serde::{Deserialize, Serialize};
 use std::fmt;
 use std::fmt::Formatter;
-use std::io::{BufReader, Cursor, Write};
+use std::io::{BufReader, BufWriter, Write};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tracing::instrument;
 
-pub const DLURL: &str = "https://go.dev/dl/?mode=json&include=all";
-
 const KIND: &str = "archive";
 
+/// Set once fetching the list failed so the stale copy is used for the rest of the run
+static REFRESH_FAILED: AtomicBool = AtomicBool::new(false);
+
 #[cfg(target_os = "windows")]
 const OS: &str = "windows";
 
@@ -40,7 +46,26 @@ pub struct File {
 
 impl File {
     pub fn get_url(&self) -> String {
-        format!("{}/{}", DOWNLOAD_URL, self.filename)
+        self.url_at(&DOWNLOAD_BASE)
+    }
+    /// URL of the file under the `base` download URL
+    pub fn url_at(&self, base: &str) -> String {
+        format!("{}/{}", base.trim_end_matches('/'), self.filename)
+    }
+    pub fn filename(&self) -> &str {
+        &self.filename
+    }
+    pub fn sha256(&self) -> &str {
+        &self.sha256
+    }
+    pub fn os(&self) -> &str {
+        &self.os
+    }
+    pub fn arch(&self) -> &str {
+        &self.arch
+    }
+    pub fn kind(&self) -> &str {
+        &self.kind
     }
 }
 
@@ -77,21 +102,70 @@ fn default_version() -> Version {
     Version::new(0, 0, 0)
 }
 
+/// Parses go's own version strings (`go1.20`, `go1.21.5`, `go1.22rc1`) into semver,
+/// padding the missing components and turning `rcN`/`betaN` into a pre-release
+pub fn parse_go_version(src: &str) -> Result<Version> {
+    let src = src.trim();
+    let src = src.strip_prefix("go").unwrap_or(src);
+    if let Ok(v) = Version::parse(src) {
+        return Ok(v);
+    }
+    let (nums, pre) = src.split_at(
+        src.find(|c: char| c.is_ascii_alphabetic())
+            .unwrap_or(src.len()),
+    );
+    let mut parts = nums.split('.').map(|x| x.parse::<u64>());
+    let major = parts.next().context("No major version")??;
+    let minor = parts.next().transpose()?.unwrap_or(0);
+    let patch = parts.next().transpose()?.unwrap_or(0);
+    if parts.next().is_some() {
+        return Err(anyhow!("Too many version components in {}", src));
+    }
+    let mut ret = Version::new(major, minor, patch);
+    if !pre.is_empty() {
+        let (kind, num) = pre.split_at(pre.find(|c: char| c.is_ascii_digit()).unwrap_or(pre.len()));
+        ret.pre = if num.is_empty() {
+            Prerelease::new(kind)?
+        } else {
+            Prerelease::new(&format!("{}.{}", kind, num))?
+        };
+    }
+    Ok(ret)
+}
+
+/// Version of a release archive named like `go1.21.5.linux-amd64.tar.gz`
+pub fn archive_version(filename: &str) -> Result<Version> {
+    let rest = filename
+        .strip_prefix("go")
+        .with_context(|| format!("{} is not a golang archive", filename))?;
+    let end = rest
+        .char_indices()
+        .find(|&(i, c)| c == '.' && rest[i + 1..].starts_with(|c: char| c.is_ascii_alphabetic()))
+        .map(|(i, _)| i)
+        .unwrap_or(rest.len());
+    parse_go_version(&rest[..end])
+        .with_context(|| format!("Can't get the version from {}", filename))
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct GoVersions {
-    latest: GoVersion,
     path: PathBuf,
+    /// Unix timestamp of when the list was fetched
+    #[serde(default)]
+    fetched: Option<u64>,
+    /// Newest stable release, a mirror exporting only betas and rcs has none
+    #[serde(default)]
+    latest: Option<GoVersion>,
     pub versions: Vec<GoVersion>,
 }
 
 impl fmt::Display for GoVersions {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "Latest {}\nGot {} versions",
-            self.latest,
-            self.versions.len()
-        )
+        match &self.latest {
+            Some(l) => writeln!(f, "Latest {}", l)?,
+            None => writeln!(f, "No stable release")?,
+        }
+        write!(f, "Got {} versions", self.versions.len())
     }
 }
 
@@ -102,77 +176,258 @@ impl Default for GoVersions {
 }
 
 pub enum Downloaded {
-    File { dir: PathBuf, vers: GoVersion },
-    Mem { buf: Vec<u8>, vers: GoVersion },
+    File {
+        dir: PathBuf,
+        vers: GoVersion,
+    },
+    /// Not fetched yet, it's downloaded and unpacked in one go by `unpack`
+    Stream {
+        file: File,
+        vers: GoVersion,
+        retry: Retry,
+    },
 }
 
 impl Downloaded {
+    /// Wraps a local archive after checking it against `sha256`, or the checksum
+    /// from `versions` when that's not given, the version comes from the file name
+    pub(crate) fn from_archive(
+        path: &Path,
+        versions: Option<&GoVersions>,
+        sha256: Option<&str>,
+    ) -> Result<Self> {
+        let filename = path
+            .file_name()
+            .and_then(|x| x.to_str())
+            .context("Archive path has no file name")?;
+        let parsed = archive_version(filename)?;
+        let listed = versions.and_then(|x| x.chosen_version(parsed.clone()).ok());
+        let expected = match sha256 {
+            Some(s) => s.to_lowercase(),
+            None => listed
+                .as_ref()
+                .and_then(|x| x.files.iter().find(|f| f.filename == filename))
+                .map(|f| f.sha256.clone())
+                .with_context(|| {
+                    format!(
+                        "No checksum for {} in the version list, pass --sha256",
+                        filename
+                    )
+                })?,
+        };
+        let actual = sha256_file(path)?;
+        if actual != expected {
+            return Err(anyhow!(
+                "Checksum mismatch for {}, expected {} got {}",
+                filename,
+                expected,
+                actual
+            ));
+        }
+        let vers = listed.unwrap_or_else(|| GoVersion {
+            version: format!("go{}", parsed),
+            stable: parsed.pre.is_empty(),
+            parsed,
+            is_parsed: true,
+            files: Vec::new(),
+        });
+        Ok(Self::File {
+            dir: path.to_path_buf(),
+            vers,
+        })
+    }
+    pub(crate) fn version(&self) -> &GoVersion {
+        match self {
+            Self::File { vers, .. } | Self::Stream { vers, .. } => vers,
+        }
+    }
     #[instrument(err, ret, skip(self))]
-    pub(crate) fn unpack(&self, path: &Path, rename: bool) -> Result<()> {
-        let par = path.parent().context("No parent")?;
-        let vers = match self {
-            Self::Mem { buf, vers } => {
-                let mut r = ToDecompress::new(Cursor::new(buf))?;
-                r.extract(path)?;
-                vers
-            }
-            Self::File { dir, vers } => {
-                let mut r = ToDecompress::new(BufReader::new(std::fs::File::open(dir)?))?;
-                r.extract(path)?;
-                vers
+    pub(crate) fn unpack(&self, path: &Path) -> Result<()> {
+        match self {
+            Self::File { dir, .. } => {
+                let mut r =
+                    ToDecompress::new(BufReader::new(std::fs::File::open(dir)?), Some(dir))?;
+                r.extract(path)
             }
-        };
-        if rename {
-            std::fs::rename(par.join("go"), par.join(&format!("go{}", vers.version)))
-                .with_context(|| "Rename error".to_string())
-        } else {
-            Ok(())
+            Self::Stream { file, retry, .. } => stream_unpack(file, path, *retry),
         }
     }
 }
 
+/// Downloads `f` and unpacks it next to `path` as the bytes arrive, holding at most
+/// `STREAM_BUFFER` bytes of the archive in memory, fails once it's through when the
+/// checksum doesn't match so the caller has to unpack into a staging dir, the archive
+/// is kept in the cache as well
+#[instrument(err, skip(f), fields(file = f.filename()))]
+fn stream_unpack(f: &File, path: &Path, retry: Retry) -> Result<()> {
+    let url = f.get_url();
+    let (size, _) = probe(&url, retry)?;
+    let res = RangeReader::new(&url, 0, size, retry);
+    let bar = progress_bar(size)?;
+    // the archive is written to the cache on the way so a reinstall doesn't download it again
+    let target = ArchiveCache::new()?.target_for(f)?;
+    let partial = target.with_file_name(format!(".{}.stream", f.filename()));
+    let out = BufWriter::new(std::fs::File::create(&partial)?);
+    let mut reader = TeeReader::new(Sha256Reader::new(bar.wrap_read(res)), out);
+    let unpacked = extract_tar_gz(BufReader::with_capacity(STREAM_BUFFER, &mut reader), path);
+    bar.finish_and_clear();
+    let (hasher, out) = reader.into_parts();
+    let actual = hasher.finish();
+    let res = unpacked.and_then(|_| {
+        if actual != f.sha256 {
+            return Err(anyhow!(
+                "Checksum mismatch for {}, expected {} got {}",
+                f.filename(),
+                f.sha256,
+                actual
+            ));
+        }
+        out.into_inner().map_err(|x| x.into_error())?.sync_all()?;
+        Ok(())
+    });
+    if let Err(e) = res {
+        let _ = std::fs::remove_file(&partial);
+        return Err(e);
+    }
+    if let Err(e) = std::fs::rename(&partial, &target) {
+        paris::warn!("Failed to cache {}: {}", f.filename(), e);
+    }
+    Ok(())
+}
+
 impl GoVersions {
     #[instrument(err, ret)]
     pub fn new(path: PathBuf) -> Result<Self> {
-        if path.exists() {
-            return Self::from_file(&path);
-        }
-        let rels: Vec<GoVersion> = CLIENT
-            .get(DLURL)
-            .send()?
-            .json()
-            .context("Failed to deserialize")?;
+        let cached = if path.exists() {
+            Self::from_file(&path).ok()
+        } else {
+            None
+        };
+        match cached {
+            Some(c) if !c.is_stale() => Ok(c),
+            Some(c) if REFRESH_FAILED.load(Ordering::Relaxed) => Ok(c),
+            Some(c) => Self::fetch(path).or_else(|e| {
+                paris::warn!(
+                    "Failed to refresh the version list, using the cached one: {}",
+                    e
+                );
+                Ok(c)
+            }),
+            None => Self::fetch(path),
+        }
+    }
+    /// Fetches the list from `LIST_URL` and saves it to `path`
+    pub fn fetch(path: PathBuf) -> Result<Self> {
+        Self::fetch_from(path, &LIST_URL)
+    }
+    /// Fetches the list from `url` and saves it to `path`
+    #[instrument(err, ret(Display))]
+    pub fn fetch_from(path: PathBuf, url: &str) -> Result<Self> {
+        let rels: Vec<GoVersion> = client()
+            .inspect_err(|_| REFRESH_FAILED.store(true, Ordering::Relaxed))?
+            .get(url)
+            .send()
+            .and_then(|x| x.json())
+            .inspect_err(|_| REFRESH_FAILED.store(true, Ordering::Relaxed))?;
+        let mut ret = Self::from_releases(path, rels)?;
+        ret.fetched = Some(
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|x| x.as_secs())
+                .unwrap_or_default(),
+        );
+        if let Err(e) = ret.save(&ret.path) {
+            paris::warn!("Failed to save the version list: {}", e);
+        }
+        Ok(ret)
+    }
+    /// Whether the list is older than `LIST_TTL`
+    pub fn is_stale(&self) -> bool {
+        let Some(fetched) = self.fetched else {
+            return true;
+        };
+        let age = SystemTime::now()
+            .duration_since(UNIX_EPOCH + Duration::from_secs(fetched))
+            .unwrap_or_default();
+        age > *LIST_TTL
+    }
+    /// Parses the releases and sorts them newest first
+    pub(crate) fn from_releases(path: PathBuf, rels: Vec<GoVersion>) -> Result<Self> {
         let mut parsed: Vec<GoVersion> = rels
             .into_par_iter()
             .filter_map(|x| x.parse().ok())
             .collect();
-        parsed.par_sort_unstable_by(|a, b| a.parsed.cmp(&b.parsed));
-        let latest = parsed.get(0).context("No latest found")?;
+        if parsed.is_empty() {
+            return Err(anyhow!("No releases found"));
+        }
+        parsed.par_sort_unstable_by(|a, b| b.parsed.cmp(&a.parsed));
+        let latest = parsed.iter().find(|x| x.stable).cloned();
         Ok(Self {
-            latest: latest.clone(),
             path,
+            fetched: None,
+            latest,
             versions: parsed,
         })
     }
     #[instrument(err, ret(Display))]
     pub(crate) fn from_file(path: &Path) -> Result<Self> {
         let read = std::fs::read_to_string(path)?;
-        Ok(serde_json::from_str(read.as_str())?)
+        let ret: Self = serde_json::from_str(read.as_str())?;
+        let mut parsed = Self::from_releases(path.to_path_buf(), ret.versions)?;
+        parsed.fetched = ret.fetched;
+        Ok(parsed)
+    }
+    /// Copy of the list holding only the releases and files the filters keep
+    pub fn subset<V, F>(&self, path: PathBuf, keep: V, keep_file: F) -> Result<Self>
+    where
+        V: Fn(&GoVersion) -> bool,
+        F: Fn(&File) -> bool,
+    {
+        let rels = self
+            .versions
+            .iter()
+            .filter(|x| keep(x))
+            .cloned()
+            .map(|mut x| {
+                x.retain_files(&keep_file);
+                x
+            })
+            .filter(|x| !x.files.is_empty())
+            .collect();
+        let mut ret = Self::from_releases(path, rels)?;
+        ret.fetched = self.fetched;
+        Ok(ret)
     }
     #[instrument(err)]
-    fn save(&self, list_path: &Path) -> Result<()> {
+    pub(crate) fn save(&self, list_path: &Path) -> Result<()> {
         let mut file = std::fs::OpenOptions::new()
             .truncate(true)
             .create(true)
             .write(true)
             .open(list_path)?;
-        let to_write = toml::to_string_pretty(self)?;
+        let to_write = serde_json::to_string(self)?;
         file.write_all(to_write.as_bytes())?;
         file.sync_all()?;
         Ok(())
     }
-    pub fn latest(&self) -> GoVersion {
-        self.latest.clone()
+    pub fn len(&self) -> usize {
+        self.versions.len()
+    }
+    /// Newest stable release
+    pub fn latest(&self) -> Result<GoVersion> {
+        self.latest
+            .clone()
+            .context("The version list has no stable release")
+    }
+    /// Newest beta or release candidate
+    pub fn latest_prerelease(&self) -> Option<&GoVersion> {
+        self.versions.iter().find(|x| !x.stable)
+    }
+    /// Newest stable release of the given minor line
+    pub fn latest_for_minor(&self, major: u64, minor: u64) -> Option<&GoVersion> {
+        self.versions
+            .iter()
+            .find(|x| x.stable && x.parsed.major == major && x.parsed.minor == minor)
     }
 
     pub fn chosen_version(&self, vers: Version) -> Result<GoVersion> {
@@ -186,38 +441,114 @@ impl GoVersions {
 }
 
 impl GoVersion {
-    /// Downloads the required version
-    pub fn download(&self, output: Option<PathBuf>, workers: u8) -> Result<Downloaded> {
-        let style = manic::ProgressStyle::default_bar()
-            .template("{spinner:.green} [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({eta})")
-            .progress_chars("#>-");
-        let f = self.wanted_file()?;
-        let mut client = Downloader::new(f.get_url().as_str(), workers)?;
-        client.progress_bar();
-        let hash = manic::Hash::new_sha256(f.sha256.to_string());
-        client.verify(hash);
-        client.bar_style(style);
-        if let Some(path) = output {
-            let path_str = path.to_str().context("No path")?;
-            let filename = client.filename().to_string();
-            client.download_and_save(path_str)?;
-            Ok(Downloaded::File {
-                dir: path.join(filename),
+    /// Downloads the required version, going through the archive cache
+    pub fn download(
+        &self,
+        output: Option<PathBuf>,
+        workers: u8,
+        retries: u32,
+    ) -> Result<Downloaded> {
+        self.download_file(self.wanted_file()?, output, workers, retries)
+    }
+    /// Downloads one of this version's files, going through the archive cache
+    pub fn download_file(
+        &self,
+        f: &File,
+        output: Option<PathBuf>,
+        workers: u8,
+        retries: u32,
+    ) -> Result<Downloaded> {
+        let cache = ArchiveCache::new()?;
+        if let Some(cached) = cache.get(f) {
+            paris::info!("Using cached archive {}", cached.display());
+            let dir = match output {
+                Some(path) => {
+                    let target = path.join(f.filename());
+                    std::fs::copy(&cached, &target)?;
+                    target
+                }
+                None => cached,
+            };
+            return Ok(Downloaded::File {
+                dir,
                 vers: self.clone(),
-            })
-        } else {
-            let res = client.download()?;
-            Ok(Downloaded::Mem {
-                buf: res.to_vec(),
+            });
+        }
+        let streamable = Format::from_name(Path::new(f.filename())) == Some(Format::TarGz);
+        let retry = Retry::new(retries);
+        if output.is_none() && *STREAM && streamable {
+            return Ok(Downloaded::Stream {
+                file: f.clone(),
                 vers: self.clone(),
-            })
+                retry,
+            });
         }
+        // without an output dir the archive is downloaded straight into the cache
+        let target = match &output {
+            Some(path) => path.join(f.filename()),
+            None => cache.target_for(f)?,
+        };
+        download(&f.get_url(), f.sha256(), &target, workers, retry)?;
+        if output.is_some() {
+            if let Err(e) = cache.put_file(f, &target) {
+                paris::warn!("Failed to cache {}: {}", f.filename(), e);
+            }
+        }
+        Ok(Downloaded::File {
+            dir: target,
+            vers: self.clone(),
+        })
+    }
+    pub fn is_stable(&self) -> bool {
+        self.stable
+    }
+    pub fn files(&self) -> &[File] {
+        &self.files
+    }
+    /// Drops the files `keep` returns false for
+    pub fn retain_files<F: FnMut(&File) -> bool>(&mut self, keep: F) {
+        self.files.retain(keep);
     }
     pub fn wanted_file(&self) -> Result<&File> {
+        self.file_for(OS, &ARCH, KIND)
+    }
+    /// The file of the given kind built for `os`/`arch`, source files have neither so
+    /// those are ignored for `source`
+    pub fn file_for(&self, os: &str, arch: &str, kind: &str) -> Result<&File> {
+        if !self.files.iter().any(|x| x.kind == kind) {
+            return Err(anyhow!(
+                "{} has no {} files, it has {}",
+                self.version,
+                kind,
+                self.files
+                    .iter()
+                    .map(|x| x.kind.as_str())
+                    .unique()
+                    .join(", ")
+            ));
+        }
         self.files
-            .par_iter()
-            .find_any(|x| x.os == OS && x.arch == ARCH.as_str() && x.kind == KIND)
-            .context("No file found")
+            .iter()
+            .find(|x| x.kind == kind && (kind == "source" || (x.os == os && x.arch == arch)))
+            .with_context(|| {
+                format!(
+                    "{} has no {} for {}/{}, it has {}",
+                    self.version,
+                    kind,
+                    os,
+                    arch,
+                    self.platforms(kind).join(", ")
+                )
+            })
+    }
+    /// The `os/arch` pairs there are files of `kind` for
+    pub fn platforms(&self, kind: &str) -> Vec<String> {
+        self.files
+            .iter()
+            .filter(|x| x.kind == kind)
+            .map(|x| format!("{}/{}", x.os, x.arch))
+            .unique()
+            .collect()
     }
     #[instrument(err, ret)]
     pub(crate) fn check_newer(&self, path: &Path) -> Result<bool> {
@@ -236,15 +567,201 @@ impl GoVersion {
         if self.is_parsed {
             Ok(self)
         } else {
-            self.parsed = Version::parse(
-                &self
-                    .version
-                    .replace("go", "")
-                    .replace("rc", "-rc.")
-                    .replace("beta", "-beta."),
-            )?;
+            self.parsed = parse_go_version(&self.version)?;
             self.is_parsed = true;
             Ok(self)
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn release(version: &str, stable: bool) -> GoVersion {
+        GoVersion {
+            version: version.to_string(),
+            stable,
+            ..Default::default()
+        }
+    }
+
+    fn versions() -> GoVersions {
+        let rels = vec![
+            release("go1.20", true),
+            release("go1.21rc2", false),
+            release("go1.21.5", true),
+            release("go1.21.0", true),
+            release("go1.23rc1", false),
+            release("go1.22.1", true),
+            release("go1.22.0", true),
+        ];
+        GoVersions::from_releases(PathBuf::new(), rels).unwrap()
+    }
+
+    #[test]
+    fn parses_go_versions() {
+        assert_eq!(
+            parse_go_version("go1.21.5").unwrap(),
+            Version::new(1, 21, 5)
+        );
+        assert_eq!(parse_go_version("go1.20").unwrap(), Version::new(1, 20, 0));
+        assert_eq!(
+            parse_go_version("go1.22rc1").unwrap(),
+            Version::parse("1.22.0-rc.1").unwrap()
+        );
+        assert_eq!(
+            parse_go_version("go1.9.2beta2").unwrap(),
+            Version::parse("1.9.2-beta.2").unwrap()
+        );
+        assert!(parse_go_version("go1.2.3.4").is_err());
+    }
+
+    #[test]
+    fn sorts_newest_first() {
+        let v = versions();
+        let order: Vec<&str> = v.versions.iter().map(|x| x.version.as_str()).collect();
+        assert_eq!(
+            order,
+            [
+                "go1.23rc1",
+                "go1.22.1",
+                "go1.22.0",
+                "go1.21.5",
+                "go1.21.0",
+                "go1.21rc2",
+                "go1.20"
+            ]
+        );
+    }
+
+    #[test]
+    fn latest_is_newest_stable() {
+        assert_eq!(versions().latest().unwrap().version, "go1.22.1");
+    }
+
+    #[test]
+    fn lists_without_stable_releases() {
+        let rels = vec![release("go1.23rc1", false), release("go1.23rc2", false)];
+        let v = GoVersions::from_releases(PathBuf::new(), rels).unwrap();
+        assert!(v.latest().is_err());
+        assert_eq!(v.latest_prerelease().unwrap().version, "go1.23rc2");
+        assert!(GoVersions::from_releases(PathBuf::new(), Vec::new()).is_err());
+    }
+
+    #[test]
+    fn latest_prerelease() {
+        assert_eq!(versions().latest_prerelease().unwrap().version, "go1.23rc1");
+    }
+
+    #[test]
+    fn latest_for_minor() {
+        let v = versions();
+        assert_eq!(v.latest_for_minor(1, 21).unwrap().version, "go1.21.5");
+        assert_eq!(v.latest_for_minor(1, 20).unwrap().version, "go1.20");
+        assert!(v.latest_for_minor(1, 23).is_none());
+    }
+
+    #[test]
+    fn archive_versions() {
+        assert_eq!(
+            archive_version("go1.21.5.linux-amd64.tar.gz").unwrap(),
+            Version::new(1, 21, 5)
+        );
+        assert_eq!(
+            archive_version("go1.22rc1.windows-arm64.zip").unwrap(),
+            Version::parse("1.22.0-rc.1").unwrap()
+        );
+        assert_eq!(
+            archive_version("go1.20.src.tar.gz").unwrap(),
+            Version::new(1, 20, 0)
+        );
+        assert!(archive_version("node-v20.tar.gz").is_err());
+    }
+
+    #[test]
+    fn archive_checksum() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("go1.21.5.linux-amd64.tar.gz");
+        std::fs::write(&path, b"not really an archive").unwrap();
+        let sha = sha256_file(&path).unwrap();
+        let dl = Downloaded::from_archive(&path, None, Some(&sha.to_uppercase())).unwrap();
+        assert_eq!(dl.version().parsed, Version::new(1, 21, 5));
+        assert!(Downloaded::from_archive(&path, None, Some(&"0".repeat(64))).is_err());
+        assert!(Downloaded::from_archive(&path, Some(&versions()), None).is_err());
+    }
+
+    #[test]
+    fn files_for_platforms() {
+        let file = |os: &str, arch: &str, kind: &str| File {
+            filename: format!("go1.21.5.{}-{}.{}", os, arch, kind),
+            os: os.to_string(),
+            arch: arch.to_string(),
+            sha256: String::new(),
+            size: String::new(),
+            kind: kind.to_string(),
+        };
+        let v = GoVersion {
+            version: "go1.21.5".to_string(),
+            stable: true,
+            files: vec![
+                file("linux", "amd64", "archive"),
+                file("darwin", "arm64", "archive"),
+                file("windows", "amd64", "installer"),
+                file("", "", "source"),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(
+            v.file_for("darwin", "arm64", "archive").unwrap().filename,
+            "go1.21.5.darwin-arm64.archive"
+        );
+        assert_eq!(v.file_for("linux", "amd64", "source").unwrap().os, "");
+        let err = v
+            .file_for("linux", "arm64", "archive")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("linux/amd64, darwin/arm64"), "{}", err);
+        assert!(v.file_for("linux", "amd64", "msi").is_err());
+    }
+
+    #[test]
+    fn url_at_mirror() {
+        let f = File {
+            filename: "go1.21.5.linux-amd64.tar.gz".to_string(),
+            os: "linux".to_string(),
+            arch: "amd64".to_string(),
+            sha256: String::new(),
+            size: String::new(),
+            kind: KIND.to_string(),
+        };
+        assert_eq!(
+            f.url_at("https://golang.google.cn/dl/"),
+            "https://golang.google.cn/dl/go1.21.5.linux-amd64.tar.gz"
+        );
+    }
+
+    #[test]
+    fn fetches_list_from_mirror() {
+        let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/dl/?mode=json&include=all", server.server_addr());
+        let handle = std::thread::spawn(move || {
+            let req = server.recv().unwrap();
+            let url = req.url().to_string();
+            let body =
+                serde_json::to_string(&[release("go1.21.5", true), release("go1.22rc1", false)])
+                    .unwrap();
+            req.respond(tiny_http::Response::from_string(body)).unwrap();
+            url
+        });
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("versions.json");
+        let v = GoVersions::fetch_from(path.clone(), &url).unwrap();
+        assert_eq!(handle.join().unwrap(), "/dl/?mode=json&include=all");
+        assert_eq!(v.latest().unwrap().version, "go1.21.5");
+        assert!(!v.is_stale());
+        let saved = GoVersions::from_file(&path).unwrap();
+        assert_eq!(saved.len(), 2);
+        assert_eq!(saved.fetched, v.fetched);
+    }
+}
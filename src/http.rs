@@ -0,0 +1,205 @@
+use crate::consts::setting;
+use anyhow::{Context, Result};
+use reqwest::blocking::Client;
+use reqwest::{Certificate, Identity, Proxy, Url};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use tracing::instrument;
+
+static CLIENT: OnceLock<Client> = OnceLock::new();
+
+/// Network settings, read from the config file or the matching `GOM_` env vars
+#[derive(Debug, Clone, Default)]
+pub struct NetConfig {
+    /// Proxy for every request, `HTTPS_PROXY` and `HTTP_PROXY` are used when it's not set
+    pub proxy: Option<String>,
+    /// Comma separated hosts that skip `proxy`, on top of the ones in `NO_PROXY`
+    pub no_proxy: Option<String>,
+    /// PEM bundle of extra root certificates, e.g. the one of a TLS intercepting proxy
+    pub ca_cert: Option<PathBuf>,
+    /// PEM client certificate, it may hold the private key as well
+    pub client_cert: Option<PathBuf>,
+    /// PEM private key of `client_cert` when it's kept in a separate file
+    pub client_key: Option<PathBuf>,
+}
+
+impl NetConfig {
+    pub fn load() -> Self {
+        Self {
+            proxy: setting("proxy"),
+            no_proxy: setting("no_proxy"),
+            ca_cert: setting("ca_cert"),
+            client_cert: setting("client_cert"),
+            client_key: setting("client_key"),
+        }
+    }
+    /// Hosts that bypass `proxy`, from `no_proxy` and the `NO_PROXY` env var
+    fn bypassed(&self) -> Vec<String> {
+        let env = std::env::var("NO_PROXY")
+            .or_else(|_| std::env::var("no_proxy"))
+            .unwrap_or_default();
+        [self.no_proxy.as_deref().unwrap_or_default(), env.as_str()]
+            .iter()
+            .flat_map(|x| x.split(','))
+            .map(|x| x.trim().trim_start_matches('.').to_lowercase())
+            .filter(|x| !x.is_empty())
+            .collect()
+    }
+    #[instrument(err)]
+    pub fn build(&self) -> Result<Client> {
+        let mut builder = Client::builder();
+        if let Some(proxy) = &self.proxy {
+            let url = Url::parse(proxy).with_context(|| format!("Invalid proxy {}", proxy))?;
+            let bypassed = self.bypassed();
+            builder = builder.proxy(Proxy::custom(move |target| {
+                let host = target.host_str().unwrap_or_default();
+                (!bypasses(&bypassed, host)).then(|| url.clone())
+            }));
+        }
+        if let Some(path) = &self.ca_cert {
+            for cert in read_certs(path)? {
+                builder = builder.add_root_certificate(cert);
+            }
+        }
+        if let Some(path) = &self.client_cert {
+            let mut pem =
+                fs::read(path).with_context(|| format!("Can't read {}", path.display()))?;
+            if let Some(key) = &self.client_key {
+                pem.push(b'\n');
+                pem.extend(fs::read(key).with_context(|| format!("Can't read {}", key.display()))?);
+            }
+            let identity = Identity::from_pem(&pem)
+                .with_context(|| format!("Invalid client certificate {}", path.display()))?;
+            // PEM identities are only supported by the rustls backend
+            builder = builder.use_rustls_tls().identity(identity);
+        }
+        builder.build().context("Failed to build the HTTP client")
+    }
+}
+
+/// Whether `host` matches one of the `NO_PROXY` style entries, which cover their subdomains
+fn bypasses(entries: &[String], host: &str) -> bool {
+    let host = host
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .to_lowercase();
+    entries.iter().any(|x| {
+        x == "*"
+            || host == *x
+            || host
+                .strip_suffix(x.as_str())
+                .is_some_and(|rest| rest.ends_with('.'))
+    })
+}
+
+/// Every certificate in the PEM bundle at `path`
+fn read_certs(path: &PathBuf) -> Result<Vec<Certificate>> {
+    const END: &str = "-----END CERTIFICATE-----";
+    let pem = fs::read_to_string(path).with_context(|| format!("Can't read {}", path.display()))?;
+    let certs = pem
+        .split_inclusive(END)
+        .filter(|x| x.contains(END))
+        .map(|x| {
+            Certificate::from_pem(x.as_bytes())
+                .with_context(|| format!("Invalid certificate in {}", path.display()))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    if certs.is_empty() {
+        return Err(anyhow::anyhow!("No certificates in {}", path.display()));
+    }
+    Ok(certs)
+}
+
+/// The client every request goes through, built from `NetConfig::load` on first use
+pub fn client() -> Result<&'static Client> {
+    if let Some(c) = CLIENT.get() {
+        return Ok(c);
+    }
+    let built = NetConfig::load().build()?;
+    Ok(CLIENT.get_or_init(|| built))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    #[test]
+    fn matches_no_proxy_entries() {
+        let entries: Vec<String> = ["example.com", "10.0.0.1", "::1"]
+            .iter()
+            .map(|x| x.to_string())
+            .collect();
+        assert!(bypasses(&entries, "example.com"));
+        assert!(bypasses(&entries, "dl.Example.com"));
+        assert!(!bypasses(&entries, "notexample.com"));
+        assert!(bypasses(&entries, "10.0.0.1"));
+        assert!(bypasses(&entries, "[::1]"));
+        assert!(!bypasses(&entries, "go.dev"));
+        assert!(bypasses(&["*".to_string()], "go.dev"));
+    }
+
+    #[test]
+    fn sends_requests_through_the_proxy() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let proxy = format!("http://{}", listener.local_addr().unwrap());
+        let handle = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut line = String::new();
+            BufReader::new(stream.try_clone().unwrap())
+                .read_line(&mut line)
+                .unwrap();
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok")
+                .unwrap();
+            line
+        });
+        let client = NetConfig {
+            proxy: Some(proxy),
+            no_proxy: Some("bypassed.invalid".to_string()),
+            ..Default::default()
+        }
+        .build()
+        .unwrap();
+        let body = client
+            .get("http://go.invalid/dl/")
+            .send()
+            .unwrap()
+            .text()
+            .unwrap();
+        assert_eq!(body, "ok");
+        // proxied requests carry the absolute URL
+        assert!(handle
+            .join()
+            .unwrap()
+            .starts_with("GET http://go.invalid/dl/ "));
+        assert!(client.get("http://bypassed.invalid/").send().is_err());
+    }
+
+    #[test]
+    fn rejects_bad_certificates() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("ca.pem");
+        fs::write(&path, "not a certificate").unwrap();
+        let err = NetConfig {
+            ca_cert: Some(path.clone()),
+            ..Default::default()
+        }
+        .build()
+        .unwrap_err();
+        assert!(err.to_string().contains("No certificates"), "{}", err);
+        let err = NetConfig {
+            client_cert: Some(path),
+            ..Default::default()
+        }
+        .build()
+        .unwrap_err();
+        assert!(
+            err.to_string().contains("Invalid client certificate"),
+            "{}",
+            err
+        );
+    }
+}
@@ -20,28 +20,49 @@ use commands::Opt;
 /// and downloads latest golang version to it
 #[quit::main]
 fn main() -> Result<()> {
-    setup_panic!();
+    // human-panic still builds its hook on the deprecated `PanicInfo` alias
+    #[allow(deprecated)]
+    {
+        setup_panic!();
+    }
+    if let Some(tool) = shims::invoked_as() {
+        if let Err(e) = shims::dispatch(&tool) {
+            paris::error!("Error: {}", e);
+            quit::with_code(1);
+        }
+        return Ok(());
+    }
     let opt = Opt::try_parse()?;
     #[cfg(debug_assertions)]
     let now = std::time::Instant::now();
     init_consts();
     tracing_subscriber::fmt()
         .pretty()
+        .with_writer(std::io::stderr)
         .try_init()
-        .map_err(|x| anyhow!("Failed to init the tracing subscriber: {}", x.to_string()))?;
+        .map_err(|x| anyhow!("Failed to init the tracing subscriber: {}", x))?;
     let res = opt.run();
+    #[cfg(debug_assertions)]
+    paris::info!("Execution time: {}s", now.elapsed().as_secs_f64());
     if let Err(e) = res {
         paris::error!("Error: {}", e);
+        quit::with_code(1);
     }
-    #[cfg(debug_assertions)]
-    paris::info!("Execution time: {}s", now.elapsed().as_secs_f64());
     Ok(())
 }
 
+mod cache;
 mod commands;
 mod config;
 mod consts;
 mod decompressor;
 mod envs;
 mod goversion;
+mod http;
+mod mirror;
+mod project;
+mod shell;
+mod shims;
+mod transfer;
 mod utils;
+mod version_spec;
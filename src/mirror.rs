@@ -0,0 +1,294 @@
+use crate::cache::sha256_file;
+use crate::consts::RETRIES;
+use crate::goversion::{File as GoFile, GoVersions};
+use crate::version_spec::VersionSpec;
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::instrument;
+
+/// Name of the pruned version list inside an exported dir
+pub const LIST_FILE: &str = "versions.json";
+
+/// How long `serve` waits on a client that stopped sending its request
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Largest request line plus headers `serve` reads before giving up on a client
+const MAX_HEAD: u64 = 16 * 1024;
+
+/// What `export` should put in the mirror
+#[derive(Debug, Clone)]
+pub struct Selection {
+    pub versions: Vec<VersionSpec>,
+    pub os: Vec<String>,
+    pub arch: Vec<String>,
+}
+
+impl Selection {
+    fn wants(&self, f: &GoFile) -> bool {
+        f.kind() == "archive"
+            && self.os.iter().any(|x| x == f.os())
+            && self.arch.iter().any(|x| x == f.arch())
+    }
+}
+
+/// Downloads the selected archives into `dir` and writes a pruned `versions.json` next to them
+#[instrument(err, skip(versions))]
+pub fn export(
+    versions: &GoVersions,
+    selection: &Selection,
+    dir: &Path,
+    workers: u8,
+) -> Result<GoVersions> {
+    let mut wanted = HashSet::new();
+    for spec in &selection.versions {
+        wanted.extend(spec.resolve_all(versions)?.into_iter().map(|x| x.version));
+    }
+    let list = versions
+        .subset(
+            dir.join(LIST_FILE),
+            |x| wanted.contains(&x.version),
+            |f| selection.wants(f),
+        )
+        .context("Nothing to export, no archive matches the selection")?;
+    fs::create_dir_all(dir).context("Can't create the export dir")?;
+    for v in &list.versions {
+        for f in v.files() {
+            let target = dir.join(f.filename());
+            if target.is_file() && sha256_file(&target)? == f.sha256() {
+                paris::info!("{} is already exported", f.filename());
+                continue;
+            }
+            v.download_file(f, Some(dir.to_path_buf()), workers, *RETRIES)?;
+            paris::success!("Exported {}", f.filename());
+        }
+    }
+    list.save(&dir.join(LIST_FILE))?;
+    Ok(list)
+}
+
+/// Serves an exported dir with the URL layout of go.dev/dl, the release list
+/// at `/dl/?mode=json` and every archive at `/dl/<filename>`
+pub fn serve(dir: &Path, addr: &str) -> Result<()> {
+    let mirror = Arc::new(Mirror::new(dir)?);
+    let listener = TcpListener::bind(addr).with_context(|| format!("Can't listen on {}", addr))?;
+    paris::info!(
+        "Serving {} at http://{}/dl",
+        dir.display(),
+        listener.local_addr()?
+    );
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(s) => s,
+            Err(e) => {
+                paris::warn!("{}", e);
+                continue;
+            }
+        };
+        let mirror = Arc::clone(&mirror);
+        std::thread::spawn(move || {
+            if let Err(e) = mirror.respond(stream) {
+                paris::warn!("{}", e);
+            }
+        });
+    }
+    Ok(())
+}
+
+/// An exported dir being served, one connection per request
+struct Mirror {
+    dir: PathBuf,
+    list: Vec<u8>,
+    files: HashSet<String>,
+}
+
+impl Mirror {
+    fn new(dir: &Path) -> Result<Self> {
+        let list = GoVersions::from_file(&dir.join(LIST_FILE))
+            .with_context(|| format!("{} has no {}", dir.display(), LIST_FILE))?;
+        let files = list
+            .versions
+            .iter()
+            .flat_map(|x| x.files())
+            .map(|x| x.filename().to_string())
+            .collect();
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            list: serde_json::to_vec(&list.versions)?,
+            files,
+        })
+    }
+    fn respond(&self, mut stream: TcpStream) -> Result<()> {
+        stream.set_read_timeout(Some(READ_TIMEOUT))?;
+        let mut reader = BufReader::new(stream.try_clone()?.take(MAX_HEAD));
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        let mut parts = line.split_whitespace();
+        let (method, url) = match (parts.next(), parts.next()) {
+            (Some(m), Some(u)) => (m.to_string(), u.to_string()),
+            _ => return reply(&mut stream, "400 Bad Request", &[], None),
+        };
+        let mut range = None;
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+                break;
+            }
+            if let Some((k, v)) = line.split_once(':') {
+                if k.trim().eq_ignore_ascii_case("range") {
+                    range = Some(v.trim().to_string());
+                }
+            }
+        }
+        if reader.get_ref().limit() == 0 {
+            return reply(
+                &mut stream,
+                "431 Request Header Fields Too Large",
+                &[],
+                None,
+            );
+        }
+        let head = method == "HEAD";
+        if !head && method != "GET" {
+            return reply(&mut stream, "405 Method Not Allowed", &[], None);
+        }
+        let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+        let name = path.trim_start_matches('/');
+        let name = name
+            .strip_prefix("dl")
+            .unwrap_or(name)
+            .trim_start_matches('/');
+        if name.is_empty() && query.split('&').any(|x| x == "mode=json") {
+            let mut list = &self.list[..];
+            let body = (!head).then_some(&mut list as &mut dyn Read);
+            return reply(
+                &mut stream,
+                "200 OK",
+                &[
+                    ("Content-Type", "application/json".to_string()),
+                    ("Content-Length", self.list.len().to_string()),
+                ],
+                body,
+            );
+        }
+        if !self.files.contains(name) {
+            return reply(&mut stream, "404 Not Found", &[], None);
+        }
+        let mut file = fs::File::open(self.dir.join(name))?;
+        let len = file.metadata()?.len();
+        let (status, start, size, mut headers) = match range.and_then(|x| parse_range(&x, len)) {
+            Some((start, end)) => (
+                "206 Partial Content",
+                start,
+                end - start + 1,
+                vec![("Content-Range", format!("bytes {}-{}/{}", start, end, len))],
+            ),
+            None => ("200 OK", 0, len, Vec::new()),
+        };
+        headers.push(("Content-Length", size.to_string()));
+        headers.push(("Accept-Ranges", "bytes".to_string()));
+        file.seek(SeekFrom::Start(start))?;
+        let mut body = file.take(size);
+        let body = (!head).then_some(&mut body as &mut dyn Read);
+        reply(&mut stream, status, &headers, body)
+    }
+}
+
+fn reply(
+    stream: &mut TcpStream,
+    status: &str,
+    headers: &[(&str, String)],
+    body: Option<&mut dyn Read>,
+) -> Result<()> {
+    let mut head = format!("HTTP/1.1 {}\r\nConnection: close\r\n", status);
+    if !headers.iter().any(|(k, _)| *k == "Content-Length") {
+        head.push_str("Content-Length: 0\r\n");
+    }
+    for (k, v) in headers {
+        head.push_str(&format!("{}: {}\r\n", k, v));
+    }
+    head.push_str("\r\n");
+    stream.write_all(head.as_bytes())?;
+    if let Some(body) = body {
+        std::io::copy(body, stream)?;
+    }
+    stream.flush()?;
+    Ok(())
+}
+
+/// Inclusive byte range out of a `bytes=start-end` header, `None` when it doesn't fit in `len`
+fn parse_range(value: &str, len: u64) -> Option<(u64, u64)> {
+    let (start, end) = value.trim().strip_prefix("bytes=")?.split_once('-')?;
+    let (start, end) = match (start.trim(), end.trim()) {
+        ("", suffix) => {
+            let suffix: u64 = suffix.parse().ok()?;
+            (len.checked_sub(suffix)?, len.checked_sub(1)?)
+        }
+        (start, "") => (start.parse().ok()?, len.checked_sub(1)?),
+        (start, end) => (
+            start.parse().ok()?,
+            end.parse::<u64>().ok()?.min(len.checked_sub(1)?),
+        ),
+    };
+    (start <= end && end < len).then_some((start, end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_ranges() {
+        assert_eq!(parse_range("bytes=0-99", 1000), Some((0, 99)));
+        assert_eq!(parse_range("bytes=900-", 1000), Some((900, 999)));
+        assert_eq!(parse_range("bytes=-100", 1000), Some((900, 999)));
+        assert_eq!(parse_range("bytes=500-2000", 1000), Some((500, 999)));
+        assert_eq!(parse_range("bytes=1000-", 1000), None);
+        assert_eq!(parse_range("bytes=5-1", 1000), None);
+        assert_eq!(parse_range("items=0-1", 1000), None);
+        assert_eq!(parse_range("bytes=0-5", 0), None);
+    }
+
+    #[test]
+    fn serves_ranges_and_list() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().to_path_buf();
+        let name = "go1.21.5.linux-amd64.tar.gz";
+        fs::write(dir.join(name), b"0123456789").unwrap();
+        let mirror = Arc::new(Mirror {
+            dir: dir.clone(),
+            list: b"[]".to_vec(),
+            files: HashSet::from([name.to_string()]),
+        });
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}/dl", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                mirror.respond(stream.unwrap()).unwrap();
+            }
+        });
+        let client = reqwest::blocking::Client::new();
+        let res = client
+            .get(format!("{}/{}", base, name))
+            .header("Range", "bytes=2-5")
+            .send()
+            .unwrap();
+        assert_eq!(res.status().as_u16(), 206);
+        assert_eq!(res.bytes().unwrap().as_ref(), b"2345");
+        let list = client
+            .get(format!("{}/?mode=json&include=all", base))
+            .send()
+            .unwrap();
+        assert_eq!(list.text().unwrap(), "[]");
+        let missing = client
+            .get(format!("{}/../{}", base, LIST_FILE))
+            .send()
+            .unwrap();
+        assert_eq!(missing.status().as_u16(), 404);
+    }
+}
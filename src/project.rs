@@ -0,0 +1,177 @@
+use crate::goversion::parse_go_version;
+use crate::version_spec::VersionSpec;
+use anyhow::{anyhow, Context, Result};
+use semver::VersionReq;
+use std::fmt;
+use std::fmt::Formatter;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const VERSION_FILE: &str = ".go-version";
+pub const MOD_FILE: &str = "go.mod";
+pub const WORK_FILE: &str = "go.work";
+
+/// A version pinned by a project file
+#[derive(Debug, Clone)]
+pub struct Pin {
+    pub spec: VersionSpec,
+    pub source: PathBuf,
+}
+
+impl fmt::Display for Pin {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (set by {})", self.spec, self.source.display())
+    }
+}
+
+impl Pin {
+    /// Walks up from `start` looking for a `.go-version`, `go.work` or `go.mod` file,
+    /// a `go.work` above the module root still wins over its `go.mod` like it does for go
+    pub fn find(start: &Path) -> Result<Option<Self>> {
+        let mut module: Option<PathBuf> = None;
+        for dir in start.ancestors() {
+            let file = dir.join(VERSION_FILE);
+            if module.is_none() && file.is_file() {
+                return Self::from_file(&file).map(Some);
+            }
+            let file = dir.join(WORK_FILE);
+            if file.is_file() {
+                return Self::from_mod_file(&file).map(Some);
+            }
+            let file = dir.join(MOD_FILE);
+            if module.is_none() && file.is_file() {
+                module = Some(file);
+            }
+        }
+        module.map(|x| Self::from_mod_file(&x)).transpose()
+    }
+    /// Reads the `toolchain` and `go` directives of a `go.mod` or `go.work` file,
+    /// `toolchain` is taken as is while `go` picks the newest patch release of its minor
+    pub fn from_mod_file(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let mut go = None;
+        let mut toolchain = None;
+        for line in content.lines() {
+            let line = line.split("//").next().unwrap_or_default().trim();
+            let mut words = line.split_whitespace();
+            match (words.next(), words.next()) {
+                (Some("go"), Some(v)) => go = Some(v),
+                (Some("toolchain"), Some(v)) if v != "default" => toolchain = Some(v),
+                _ => {}
+            }
+        }
+        let spec = match (toolchain, go) {
+            (Some(t), _) => VersionSpec::Exact(parse_go_version(t)?),
+            (None, Some(g)) => {
+                let min = parse_go_version(g)?;
+                VersionSpec::Req(VersionReq::parse(&format!(
+                    ">={}, <{}.{}.0",
+                    min,
+                    min.major,
+                    min.minor + 1
+                ))?)
+            }
+            (None, None) => {
+                return Err(anyhow!(
+                    "{} has neither a go nor a toolchain directive",
+                    path.display()
+                ))
+            }
+        };
+        Ok(Self {
+            spec,
+            source: path.to_path_buf(),
+        })
+    }
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let line = content
+            .lines()
+            .map(str::trim)
+            .find(|x| !x.is_empty() && !x.starts_with('#'))
+            .with_context(|| format!("{} is empty", path.display()))?;
+        let spec = line
+            .parse()
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        Ok(Self {
+            spec,
+            source: path.to_path_buf(),
+        })
+    }
+    /// Looks for a pin starting at the working directory
+    pub fn from_cwd() -> Result<Option<Self>> {
+        Self::find(&std::env::current_dir()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use semver::Version;
+
+    fn mod_spec(content: &str) -> VersionSpec {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join(MOD_FILE);
+        fs::write(&path, content).unwrap();
+        Pin::from_mod_file(&path).unwrap().spec
+    }
+
+    #[test]
+    fn reads_toolchain_directive() {
+        let spec = mod_spec("// go 1.19 in a comment\nmodule example.com/x\n\ngo 1.21 // minimum\ntoolchain go1.21.5 // pinned\n",
+        );
+        assert_eq!(spec, VersionSpec::Exact(Version::new(1, 21, 5)));
+    }
+
+    #[test]
+    fn maps_go_directive_to_its_minor() {
+        let spec = mod_spec("module example.com/x\n\ngo 1.21\n");
+        assert_eq!(
+            spec,
+            VersionSpec::Req(VersionReq::parse(">=1.21.0, <1.22.0").unwrap())
+        );
+        let spec = mod_spec("module example.com/x\ngo 1.21.3\ntoolchain default\n");
+        assert_eq!(
+            spec,
+            VersionSpec::Req(VersionReq::parse(">=1.21.3, <1.22.0").unwrap())
+        );
+    }
+
+    #[test]
+    fn rejects_mod_without_directives() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        fs::write(dir.join(MOD_FILE), "module example.com/x\n// go 1.21\n").unwrap();
+        assert!(Pin::from_mod_file(&dir.join(MOD_FILE)).is_err());
+    }
+
+    #[test]
+    fn walks_up_to_the_nearest_pin() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let deep = dir.join("a/b/c");
+        fs::create_dir_all(&deep).unwrap();
+        fs::write(dir.join("a").join(VERSION_FILE), "# pinned\n1.20.14\n").unwrap();
+        let pin = Pin::find(&deep).unwrap().unwrap();
+        assert_eq!(pin.spec, VersionSpec::Exact(Version::new(1, 20, 14)));
+        assert_eq!(pin.source, dir.join("a").join(VERSION_FILE));
+        fs::write(dir.join("a/b").join(MOD_FILE), "go 1.22\n").unwrap();
+        let pin = Pin::find(&deep).unwrap().unwrap();
+        assert_eq!(pin.source, dir.join("a/b").join(MOD_FILE));
+    }
+
+    #[test]
+    fn work_file_wins_over_mod_file() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let module = dir.join("module");
+        fs::create_dir_all(&module).unwrap();
+        fs::write(module.join(MOD_FILE), "go 1.21\n").unwrap();
+        fs::write(dir.join(WORK_FILE), "go 1.22\ntoolchain go1.22.1\n").unwrap();
+        let pin = Pin::find(&module).unwrap().unwrap();
+        assert_eq!(pin.source, dir.join(WORK_FILE));
+        assert_eq!(pin.spec, VersionSpec::Exact(Version::new(1, 22, 1)));
+    }
+}
@@ -0,0 +1,202 @@
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+/// Shells we can emit code for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    Nu,
+    Pwsh,
+}
+
+/// Single quotes `s` for POSIX shells
+fn quote_posix(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+/// Single quotes `s` for fish, where `\` and `'` are the only escapes inside quotes
+fn quote_fish(s: &str) -> String {
+    format!("'{}'", s.replace('\\', r"\\").replace('\'', r"\'"))
+}
+
+/// Double quotes `s` for nushell, its single quoted strings can't contain a `'`
+fn quote_nu(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', r"\\").replace('"', r#"\""#))
+}
+
+/// Single quotes `s` for PowerShell, where a `'` is escaped by doubling it
+fn quote_pwsh(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+impl Shell {
+    /// Guesses the shell from `$SHELL`, PowerShell on Windows
+    pub fn detect() -> Self {
+        let name = std::env::var_os("SHELL").and_then(|x| {
+            Path::new(&x)
+                .file_stem()
+                .map(|x| x.to_string_lossy().to_string())
+        });
+        match name.as_deref() {
+            Some("zsh") => Self::Zsh,
+            Some("fish") => Self::Fish,
+            Some("nu") => Self::Nu,
+            Some("pwsh") | Some("powershell") => Self::Pwsh,
+            Some(_) => Self::Bash,
+            None if cfg!(windows) => Self::Pwsh,
+            None => Self::Bash,
+        }
+    }
+    pub fn quote(&self, s: &str) -> String {
+        match self {
+            Self::Bash | Self::Zsh => quote_posix(s),
+            Self::Fish => quote_fish(s),
+            Self::Nu => quote_nu(s),
+            Self::Pwsh => quote_pwsh(s),
+        }
+    }
+    pub fn set(&self, key: &str, value: &str) -> String {
+        let value = self.quote(value);
+        match self {
+            Self::Bash | Self::Zsh => format!("export {}={};\n", key, value),
+            Self::Fish => format!("set -gx {} {};\n", key, value),
+            Self::Nu => format!("$env.{} = {}\n", key, value),
+            Self::Pwsh => format!("$env:{} = {};\n", key, value),
+        }
+    }
+    pub fn unset(&self, key: &str) -> String {
+        match self {
+            Self::Bash | Self::Zsh => format!("unset {};\n", key),
+            Self::Fish => format!("set -e {};\n", key),
+            Self::Nu => format!("hide-env -i {}\n", key),
+            Self::Pwsh => format!("Remove-Item Env:{} -ErrorAction SilentlyContinue;\n", key),
+        }
+    }
+    /// Fails when a shell joining `PATH` itself gets an entry holding the separator
+    pub fn set_path(&self, paths: &[PathBuf]) -> Result<String> {
+        let items: Vec<String> = paths
+            .iter()
+            .map(|x| self.quote(&x.to_string_lossy()))
+            .collect();
+        Ok(match self {
+            Self::Fish => format!("set -gx PATH {};\n", items.join(" ")),
+            Self::Nu => format!("$env.PATH = [{}]\n", items.join(", ")),
+            Self::Bash | Self::Zsh | Self::Pwsh => {
+                let joined =
+                    std::env::join_paths(paths).map_err(|e| anyhow!("Can't build PATH: {}", e))?;
+                self.set("PATH", &joined.to_string_lossy())
+            }
+        })
+    }
+    /// Prints `msg` on stderr once the output is evaluated
+    pub fn warn<D: Display>(&self, msg: D) -> String {
+        let msg = self.quote(&msg.to_string());
+        match self {
+            Self::Bash | Self::Zsh | Self::Fish => format!("echo {} >&2;\n", msg),
+            Self::Nu => format!("print -e {}\n", msg),
+            Self::Pwsh => format!("[Console]::Error.WriteLine({});\n", msg),
+        }
+    }
+    /// Code re-running `hook-env` whenever the working directory changes
+    pub fn hook(&self, exe: &Path) -> Result<String> {
+        let exe = self.quote(&exe.to_string_lossy());
+        Ok(match self {
+            Self::Bash => format!(
+                r#"_gom_hook() {{
+    if [ "${{_GOM_LAST_PWD:-}}" != "$PWD" ]; then
+        _GOM_LAST_PWD="$PWD"
+        eval "$({exe} hook-env bash 2>/dev/null)"
+    fi
+}}
+case ";${{PROMPT_COMMAND:-}};" in
+    *";_gom_hook;"*) ;;
+    *) PROMPT_COMMAND="_gom_hook${{PROMPT_COMMAND:+;$PROMPT_COMMAND}}" ;;
+esac
+"#
+            ),
+            Self::Zsh => format!(
+                r#"_gom_hook() {{
+    eval "$({exe} hook-env zsh 2>/dev/null)"
+}}
+typeset -ag chpwd_functions
+if (( ! ${{chpwd_functions[(I)_gom_hook]}} )); then
+    chpwd_functions=(_gom_hook $chpwd_functions)
+fi
+_gom_hook
+"#
+            ),
+            Self::Fish => format!(
+                r#"function _gom_hook --on-variable PWD
+    {exe} hook-env fish 2>/dev/null | source
+end
+_gom_hook
+"#
+            ),
+            Self::Nu | Self::Pwsh => {
+                return Err(anyhow!("The hook is only available for bash, zsh and fish"))
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRICKY: &str = "a b'c$d";
+
+    #[test]
+    fn quotes_for_bash_and_zsh() {
+        assert_eq!(Shell::Bash.quote(TRICKY), r"'a b'\''c$d'");
+        assert_eq!(Shell::Zsh.quote(TRICKY), r"'a b'\''c$d'");
+    }
+
+    #[test]
+    fn quotes_for_fish() {
+        assert_eq!(Shell::Fish.quote(TRICKY), r"'a b\'c$d'");
+        assert_eq!(Shell::Fish.quote(r"a\b"), r"'a\\b'");
+    }
+
+    #[test]
+    fn quotes_for_nu() {
+        assert_eq!(Shell::Nu.quote(TRICKY), "\"a b'c$d\"");
+        assert_eq!(Shell::Nu.quote(r#"a"b\"#), r#""a\"b\\""#);
+    }
+
+    #[test]
+    fn quotes_for_pwsh() {
+        assert_eq!(Shell::Pwsh.quote(TRICKY), "'a b''c$d'");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn sets_path() {
+        let paths = [PathBuf::from("/opt/go 1/bin"), PathBuf::from("/usr/bin")];
+        assert_eq!(
+            Shell::Bash.set_path(&paths).unwrap(),
+            "export PATH='/opt/go 1/bin:/usr/bin';\n"
+        );
+        assert_eq!(
+            Shell::Fish.set_path(&paths).unwrap(),
+            "set -gx PATH '/opt/go 1/bin' '/usr/bin';\n"
+        );
+        assert_eq!(
+            Shell::Nu.set_path(&paths).unwrap(),
+            "$env.PATH = [\"/opt/go 1/bin\", \"/usr/bin\"]\n"
+        );
+        assert_eq!(
+            Shell::Pwsh.set_path(&paths).unwrap(),
+            "$env:PATH = '/opt/go 1/bin:/usr/bin';\n"
+        );
+        let paths = [PathBuf::from("/opt/go:1/bin")];
+        assert!(Shell::Bash.set_path(&paths).is_err());
+        assert_eq!(
+            Shell::Fish.set_path(&paths).unwrap(),
+            "set -gx PATH '/opt/go:1/bin';\n"
+        );
+    }
+}
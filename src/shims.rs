@@ -0,0 +1,65 @@
+use crate::consts::SHIMMED;
+use crate::envs::EnvManager;
+use anyhow::{anyhow, Context, Result};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// The tool name if we were started through one of the shims
+pub(crate) fn invoked_as() -> Option<String> {
+    let arg0 = std::env::args_os().next()?;
+    let name = Path::new(&arg0).file_stem()?.to_str()?;
+    SHIMMED.contains(&name).then(|| name.to_string())
+}
+
+/// Runs `tool` from the env in effect, replacing this process where the OS allows it
+pub(crate) fn dispatch(tool: &str) -> Result<()> {
+    let mut manager = EnvManager::new()?;
+    let (env, _) = manager.active()?;
+    let program = env
+        .bin_dir()
+        .join(format!("{}{}", tool, std::env::consts::EXE_SUFFIX));
+    if !program.is_file() {
+        return Err(anyhow!("{} is not part of golang {}", tool, env.version));
+    }
+    let mut cmd = Command::new(program);
+    cmd.args(std::env::args_os().skip(1)).envs(env.env_vars()?);
+    exec(cmd)
+}
+
+#[cfg(unix)]
+fn exec(mut cmd: Command) -> Result<()> {
+    use std::os::unix::process::CommandExt;
+    Err(cmd.exec()).context("Failed to exec")
+}
+
+#[cfg(windows)]
+fn exec(mut cmd: Command) -> Result<()> {
+    let status = cmd.status().context("Failed to run")?;
+    quit::with_code(status.code().unwrap_or(1));
+}
+
+/// Points a shim for every tool in `SHIMMED` at our own executable
+pub(crate) fn create(dir: &Path) -> Result<()> {
+    fs::create_dir_all(dir).context("Can't create shims dir")?;
+    let exe = std::env::current_exe().context("Can't find own executable")?;
+    for tool in SHIMMED {
+        let shim = dir.join(format!("{}{}", tool, std::env::consts::EXE_SUFFIX));
+        if shim.symlink_metadata().is_ok() {
+            fs::remove_file(&shim)?;
+        }
+        link(&exe, &shim).with_context(|| format!("Failed to create {}", shim.display()))?;
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn link(exe: &Path, shim: &Path) -> Result<()> {
+    Ok(std::os::unix::fs::symlink(exe, shim)?)
+}
+
+#[cfg(windows)]
+fn link(exe: &Path, shim: &Path) -> Result<()> {
+    fs::copy(exe, shim)?;
+    Ok(())
+}
@@ -0,0 +1,492 @@
+use crate::cache::sha256_file;
+use crate::http::client;
+use anyhow::{anyhow, Context, Result};
+use indicatif::{ProgressBar, ProgressStyle};
+use reqwest::blocking::Response;
+use reqwest::header::{ACCEPT_RANGES, CONTENT_LENGTH, RANGE};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing::instrument;
+
+/// Delay before the first retry, doubled after every failure in a row
+const BACKOFF_BASE: Duration = Duration::from_millis(500);
+
+/// Longest wait between two retries
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Bytes a chunk downloads between two saves of the part state
+const SAVE_EVERY: u64 = 4 * 1024 * 1024;
+
+/// How many times a failed request is retried and how long to wait in between
+#[derive(Debug, Clone, Copy)]
+pub struct Retry {
+    pub retries: u32,
+    pub base: Duration,
+}
+
+impl Retry {
+    pub fn new(retries: u32) -> Self {
+        Self {
+            retries,
+            base: BACKOFF_BASE,
+        }
+    }
+    /// Wait before retry number `attempt`, counting from 0
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        self.base
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(MAX_BACKOFF)
+    }
+    /// Calls `f` until it succeeds or the retries run out
+    fn run<T>(&self, what: &str, mut f: impl FnMut() -> Result<T>) -> Result<T> {
+        let mut attempt = 0;
+        loop {
+            match f() {
+                Err(e) if attempt < self.retries => {
+                    let wait = self.backoff(attempt);
+                    paris::warn!("{} failed: {}, retrying in {:?}", what, e, wait);
+                    std::thread::sleep(wait);
+                    attempt += 1;
+                }
+                res => return res,
+            }
+        }
+    }
+}
+
+/// Reads `url` from `pos` up to `end`, reconnecting with a Range request from
+/// where it stopped when the connection drops
+pub(crate) struct RangeReader {
+    url: String,
+    pos: u64,
+    end: Option<u64>,
+    retry: Retry,
+    failures: u32,
+    res: Option<Response>,
+}
+
+impl RangeReader {
+    pub(crate) fn new(url: &str, pos: u64, end: Option<u64>, retry: Retry) -> Self {
+        Self {
+            url: url.to_string(),
+            pos,
+            end,
+            retry,
+            failures: 0,
+            res: None,
+        }
+    }
+    fn connect(&self) -> Result<Response> {
+        let mut req = client()?.get(&self.url);
+        if self.pos > 0 || self.end.is_some() {
+            let end = self.end.map(|x| (x - 1).to_string()).unwrap_or_default();
+            req = req.header(RANGE, format!("bytes={}-{}", self.pos, end));
+        }
+        let res = req.send()?.error_for_status()?;
+        if self.pos > 0 && res.status() != StatusCode::PARTIAL_CONTENT {
+            return Err(anyhow!("{} doesn't support resuming downloads", self.url));
+        }
+        Ok(res)
+    }
+    fn try_read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let left = self.end.map(|x| x.saturating_sub(self.pos));
+        if left == Some(0) {
+            return Ok(0);
+        }
+        let res = match self.res.take() {
+            Some(r) => r,
+            None => self.connect()?,
+        };
+        let res = self.res.insert(res);
+        let max = left.map_or(buf.len(), |x| buf.len().min(x as usize));
+        let n = res.read(&mut buf[..max])?;
+        if n == 0 && left.is_some() {
+            return Err(anyhow!("Connection closed early"));
+        }
+        Ok(n)
+    }
+}
+
+impl Read for RangeReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            match self.try_read(buf) {
+                Ok(n) => {
+                    self.pos += n as u64;
+                    self.failures = 0;
+                    return Ok(n);
+                }
+                Err(e) if self.failures < self.retry.retries => {
+                    self.res = None;
+                    let wait = self.retry.backoff(self.failures);
+                    self.failures += 1;
+                    paris::warn!(
+                        "Download interrupted at byte {}: {}, retrying in {:?}",
+                        self.pos,
+                        e,
+                        wait
+                    );
+                    std::thread::sleep(wait);
+                }
+                Err(e) => return Err(std::io::Error::other(e)),
+            }
+        }
+    }
+}
+
+/// Size of the file behind `url` and whether the server serves ranges of it
+#[instrument(err)]
+pub(crate) fn probe(url: &str, retry: Retry) -> Result<(Option<u64>, bool)> {
+    let res = retry.run(&format!("Reaching {}", url), || {
+        Ok(client()?.head(url).send()?.error_for_status()?)
+    })?;
+    let size = res
+        .headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|x| x.to_str().ok())
+        .and_then(|x| x.parse().ok());
+    let ranges = res
+        .headers()
+        .get(ACCEPT_RANGES)
+        .is_some_and(|x| x.as_bytes() == b"bytes");
+    Ok((size, ranges))
+}
+
+/// One range of the file, `end` is exclusive and unknown when the server sent no length
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct Chunk {
+    start: u64,
+    end: Option<u64>,
+    done: u64,
+}
+
+/// Progress of an unfinished download, saved next to its part file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PartState {
+    sha256: String,
+    size: Option<u64>,
+    chunks: Vec<Chunk>,
+}
+
+impl PartState {
+    fn new(sha256: &str, size: Option<u64>, chunks: u64) -> Self {
+        let chunks = match size {
+            Some(size) => {
+                let len = size.div_ceil(chunks.max(1)).max(1);
+                (0..size)
+                    .step_by(len as usize)
+                    .map(|start| Chunk {
+                        start,
+                        end: Some((start + len).min(size)),
+                        done: 0,
+                    })
+                    .collect()
+            }
+            None => vec![Chunk {
+                start: 0,
+                end: None,
+                done: 0,
+            }],
+        };
+        Self {
+            sha256: sha256.to_string(),
+            size,
+            chunks,
+        }
+    }
+    fn load(path: &Path) -> Option<Self> {
+        serde_json::from_slice(&fs::read(path).ok()?).ok()
+    }
+    fn save(&self, path: &Path) -> Result<()> {
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, serde_json::to_vec(self)?)?;
+        fs::rename(&tmp, path).context("Failed to save the download state")
+    }
+    fn done(&self) -> u64 {
+        self.chunks.iter().map(|x| x.done).sum()
+    }
+}
+
+/// Byte progress bar of a download of `size` bytes
+pub(crate) fn progress_bar(size: Option<u64>) -> Result<ProgressBar> {
+    let bar = ProgressBar::new(size.unwrap_or_default());
+    bar.set_style(
+        ProgressStyle::default_bar()
+            .template("{spinner:.green} [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({eta})")?
+            .progress_chars("#>-"),
+    );
+    Ok(bar)
+}
+
+/// Part file and state file kept next to `target` while it downloads
+fn part_paths(target: &Path) -> Result<(PathBuf, PathBuf)> {
+    let name = target
+        .file_name()
+        .and_then(|x| x.to_str())
+        .context("Download target has no file name")?;
+    let dir = target.parent().context("No parent")?;
+    Ok((
+        dir.join(format!(".{}.part", name)),
+        dir.join(format!(".{}.part.json", name)),
+    ))
+}
+
+/// Downloads `url` to `target` in up to `workers` ranges at once. Progress is kept in
+/// `.<name>.part` and `.<name>.part.json` next to it so an interrupted download carries
+/// on where it stopped, `target` only appears once the file matches `sha256`
+#[instrument(err, skip(sha256))]
+pub(crate) fn download(
+    url: &str,
+    sha256: &str,
+    target: &Path,
+    workers: u8,
+    retry: Retry,
+) -> Result<()> {
+    let (part, state_path) = part_paths(target)?;
+    let state = match PartState::load(&state_path) {
+        Some(s) if s.sha256 == sha256 && part.is_file() => {
+            paris::info!("Resuming the download of {}", url);
+            s
+        }
+        _ => {
+            let (size, ranges) = probe(url, retry)?;
+            let chunks = if ranges { workers.max(1) } else { 1 };
+            let s = PartState::new(sha256, size, chunks as u64);
+            fs::File::create(&part)?.set_len(size.unwrap_or_default())?;
+            s.save(&state_path)?;
+            s
+        }
+    };
+    let bar = progress_bar(state.size)?;
+    bar.set_position(state.done());
+    let count = state.chunks.len();
+    let state = Mutex::new(state);
+    let results: Vec<Result<()>> = std::thread::scope(|s| {
+        let handles: Vec<_> = (0..count)
+            .map(|i| {
+                let (state, part, state_path, bar) = (&state, &part, &state_path, &bar);
+                s.spawn(move || fetch_chunk(url, part, i, state, state_path, retry, bar))
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|x| {
+                x.join()
+                    .unwrap_or_else(|_| Err(anyhow!("Download thread panicked")))
+            })
+            .collect()
+    });
+    bar.finish_and_clear();
+    results.into_iter().collect::<Result<Vec<()>>>()?;
+    let actual = sha256_file(&part)?;
+    if actual != sha256 {
+        let _ = fs::remove_file(&part);
+        let _ = fs::remove_file(&state_path);
+        return Err(anyhow!(
+            "Checksum mismatch for {}, expected {} got {}",
+            url,
+            sha256,
+            actual
+        ));
+    }
+    fs::rename(&part, target)
+        .with_context(|| format!("Failed to move the download to {}", target.display()))?;
+    let _ = fs::remove_file(&state_path);
+    Ok(())
+}
+
+/// Downloads the rest of chunk `idx` into `part`, recording its progress in the state
+/// even when it fails so the next run can pick it up
+fn fetch_chunk(
+    url: &str,
+    part: &Path,
+    idx: usize,
+    state: &Mutex<PartState>,
+    state_path: &Path,
+    retry: Retry,
+    bar: &ProgressBar,
+) -> Result<()> {
+    let chunk = state.lock().map_err(|_| anyhow!("Poisoned state"))?.chunks[idx];
+    let save = |done: u64| -> Result<()> {
+        let mut s = state.lock().map_err(|_| anyhow!("Poisoned state"))?;
+        s.chunks[idx].done = done;
+        s.save(state_path)
+    };
+    let mut done = chunk.done;
+    let mut copy = || -> Result<()> {
+        let start = chunk.start + done;
+        let mut file = fs::OpenOptions::new().write(true).open(part)?;
+        file.seek(SeekFrom::Start(start))?;
+        let mut reader = RangeReader::new(url, start, chunk.end, retry);
+        let mut buf = vec![0u8; 64 * 1024];
+        let mut unsaved = 0;
+        loop {
+            let n = reader.read(&mut buf)?;
+            if n == 0 {
+                return Ok(());
+            }
+            file.write_all(&buf[..n])?;
+            done += n as u64;
+            unsaved += n as u64;
+            bar.inc(n as u64);
+            if unsaved >= SAVE_EVERY {
+                save(done)?;
+                unsaved = 0;
+            }
+        }
+    };
+    let res = copy();
+    save(done)?;
+    res.with_context(|| format!("Failed to download {}", url))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::BufRead;
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Serves `body` with range support, the first `drops` responses stop halfway
+    /// through the body and close the connection
+    fn flaky_server(body: Arc<Vec<u8>>, drops: usize) -> (String, Arc<Mutex<Vec<u64>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let starts = Arc::new(Mutex::new(Vec::new()));
+        let served = Arc::new(AtomicUsize::new(0));
+        let seen = starts.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let (body, seen, served) = (body.clone(), seen.clone(), served.clone());
+                std::thread::spawn(move || {
+                    let mut stream = stream.unwrap();
+                    let mut reader = std::io::BufReader::new(stream.try_clone().unwrap());
+                    let mut request = String::new();
+                    reader.read_line(&mut request).unwrap();
+                    let mut range = None;
+                    loop {
+                        let mut line = String::new();
+                        reader.read_line(&mut line).unwrap();
+                        if line.trim().is_empty() {
+                            break;
+                        }
+                        if let Some(r) = line.to_lowercase().strip_prefix("range: bytes=") {
+                            let (s, e) = r.trim().split_once('-').unwrap();
+                            let end = e.parse().unwrap_or(body.len() as u64 - 1);
+                            range = Some((s.parse::<u64>().unwrap(), end));
+                        }
+                    }
+                    let (start, end) = range.unwrap_or((0, body.len() as u64 - 1));
+                    let slice = &body[start as usize..=end as usize];
+                    let status = if range.is_some() {
+                        "206 Partial Content"
+                    } else {
+                        "200 OK"
+                    };
+                    let mut head = format!(
+                        "HTTP/1.1 {}\r\nAccept-Ranges: bytes\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                        status,
+                        slice.len()
+                    );
+                    if request.starts_with("HEAD") {
+                        head = head.replace(
+                            &format!("Length: {}", slice.len()),
+                            &format!("Length: {}", body.len()),
+                        );
+                        stream.write_all(head.as_bytes()).unwrap();
+                        return;
+                    }
+                    seen.lock().unwrap().push(start);
+                    stream.write_all(head.as_bytes()).unwrap();
+                    if served.fetch_add(1, Ordering::SeqCst) < drops {
+                        let _ = stream.write_all(&slice[..slice.len() / 2]);
+                        return;
+                    }
+                    let _ = stream.write_all(slice);
+                });
+            }
+        });
+        (format!("http://{}/go.tar.gz", addr), starts)
+    }
+
+    fn body() -> Arc<Vec<u8>> {
+        Arc::new((0..200_000u32).map(|x| (x % 251) as u8).collect())
+    }
+
+    fn sha(body: &[u8]) -> String {
+        use sha2::{Digest, Sha256};
+        format!("{:x}", Sha256::digest(body))
+    }
+
+    fn retry(retries: u32) -> Retry {
+        Retry {
+            retries,
+            base: Duration::from_millis(1),
+        }
+    }
+
+    #[test]
+    fn backs_off_exponentially() {
+        let r = Retry::new(5);
+        assert_eq!(r.backoff(0), Duration::from_millis(500));
+        assert_eq!(r.backoff(1), Duration::from_secs(1));
+        assert_eq!(r.backoff(3), Duration::from_secs(4));
+        assert_eq!(r.backoff(40), MAX_BACKOFF);
+    }
+
+    #[test]
+    fn retries_dropped_connections() {
+        let body = body();
+        let (url, starts) = flaky_server(body.clone(), 3);
+        let tmp = tempfile::tempdir().unwrap();
+        let target = tmp.path().join("go.tar.gz");
+        download(&url, &sha(&body), &target, 2, retry(3)).unwrap();
+        assert_eq!(fs::read(&target).unwrap(), *body);
+        // resumed requests continue past the start of their chunk
+        let starts = starts.lock().unwrap();
+        assert!(starts.len() > 2);
+        assert!(
+            starts.iter().any(|x| *x != 0 && *x != 100_000),
+            "{:?}",
+            starts
+        );
+        let (part, state) = part_paths(&target).unwrap();
+        assert!(!part.exists() && !state.exists());
+    }
+
+    #[test]
+    fn resumes_interrupted_downloads() {
+        let body = body();
+        let tmp = tempfile::tempdir().unwrap();
+        let target = tmp.path().join("go.tar.gz");
+        let (url, _) = flaky_server(body.clone(), 1);
+        download(&url, &sha(&body), &target, 1, retry(0)).unwrap_err();
+        assert!(!target.exists());
+        let (part, state) = part_paths(&target).unwrap();
+        let saved = PartState::load(&state).unwrap();
+        assert_eq!(saved.done(), 100_000);
+        let (url, starts) = flaky_server(body.clone(), 0);
+        download(&url, &sha(&body), &target, 1, retry(0)).unwrap();
+        assert_eq!(*starts.lock().unwrap(), vec![100_000]);
+        assert_eq!(fs::read(&target).unwrap(), *body);
+        assert!(!part.exists() && !state.exists());
+    }
+
+    #[test]
+    fn drops_mismatching_downloads() {
+        let body = body();
+        let (url, _) = flaky_server(body, 0);
+        let tmp = tempfile::tempdir().unwrap();
+        let target = tmp.path().join("go.tar.gz");
+        let err = download(&url, &"0".repeat(64), &target, 2, retry(0)).unwrap_err();
+        assert!(err.to_string().contains("Checksum mismatch"), "{}", err);
+        let (part, state) = part_paths(&target).unwrap();
+        assert!(!target.exists() && !part.exists() && !state.exists());
+    }
+}
@@ -1,10 +1,11 @@
 use crate::consts::{CONFIG_DIR, CONFIG_PATH, CURRENT_INSTALL, DEFAULT_INSTALL, VERSION_LIST};
 use crate::goversion::GoVersion;
+use crate::version_spec::VersionSpec;
 use crate::GoVersions;
 use anyhow::{Context, Result};
 use console::Term;
 use dialoguer::theme::ColorfulTheme;
-use dialoguer::Select;
+use dialoguer::{Confirm, Select};
 use semver::Version;
 use std::io::ErrorKind;
 
@@ -35,6 +36,17 @@ pub(crate) fn ask_for_version(term: &Term, versions: &GoVersions) -> Result<GoVe
     }
 }
 
+pub(crate) fn ask_to_install(term: &Term, spec: &VersionSpec) -> Result<bool> {
+    Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt(format!(
+            "No installed version matches {}, install it now?",
+            spec
+        ))
+        .default(true)
+        .interact_on(term)
+        .context("Failed to ask for confirmation")
+}
+
 pub(crate) fn get_local_version(path: &Path) -> Result<Option<Version>> {
     duct::cmd!(
         path.join("bin/go").to_str().context("No go exec")?,
@@ -80,3 +92,12 @@ pub(super) fn check_in_path(p: &Path) -> Result<bool> {
         .par_bridge()
         .any(|x| x == p_str))
 }
+
+/// Sums the size of every file under `p` without following symlinks
+pub(crate) fn dir_size(p: &Path) -> Result<u64> {
+    let meta = std::fs::symlink_metadata(p)?;
+    if !meta.is_dir() {
+        return Ok(meta.len());
+    }
+    std::fs::read_dir(p)?.map(|x| dir_size(&x?.path())).sum()
+}
@@ -0,0 +1,241 @@
+use crate::goversion::{parse_go_version, GoVersion, GoVersions};
+use anyhow::{anyhow, Context, Result};
+use semver::{Version, VersionReq};
+use std::fmt;
+use std::fmt::Formatter;
+use std::str::FromStr;
+
+/// What the user asked for on the command line, resolved against
+/// either the remote version list or the installed envs
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum VersionSpec {
+    /// Newest stable release
+    #[default]
+    Latest,
+    /// Alias of `Latest`
+    Stable,
+    /// Newest stable release of the minor line before the latest one
+    OldStable,
+    /// Newest beta or release candidate
+    Rc,
+    Exact(Version),
+    Req(VersionReq),
+}
+
+impl FromStr for VersionSpec {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        match s.to_lowercase().as_str() {
+            "latest" => return Ok(Self::Latest),
+            "stable" => return Ok(Self::Stable),
+            "oldstable" => return Ok(Self::OldStable),
+            "rc" => return Ok(Self::Rc),
+            _ => {}
+        }
+        if s.starts_with(['~', '^', '=', '<', '>', '*']) || s.contains(',') {
+            return VersionReq::parse(s)
+                .map(Self::Req)
+                .with_context(|| format!("Invalid version range {}", s));
+        }
+        let bare = s.strip_prefix("go").unwrap_or(s);
+        if bare.split('.').any(|x| matches!(x, "x" | "X" | "*")) {
+            return VersionReq::parse(bare)
+                .map(Self::Req)
+                .with_context(|| format!("Invalid version range {}", s));
+        }
+        let is_partial = !bare.contains(|c: char| c.is_ascii_alphabetic() || c == '-')
+            && bare.split('.').count() < 3;
+        if is_partial {
+            VersionReq::parse(&format!("~{}", bare))
+                .map(Self::Req)
+                .with_context(|| format!("Invalid version {}", s))
+        } else {
+            parse_go_version(bare)
+                .map(Self::Exact)
+                .with_context(|| format!("Invalid version {}", s))
+        }
+    }
+}
+
+impl fmt::Display for VersionSpec {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Latest => write!(f, "latest"),
+            Self::Stable => write!(f, "stable"),
+            Self::OldStable => write!(f, "oldstable"),
+            Self::Rc => write!(f, "rc"),
+            Self::Exact(v) => write!(f, "{}", v),
+            Self::Req(r) => write!(f, "{}", r),
+        }
+    }
+}
+
+impl VersionSpec {
+    /// Picks the best match out of `items`, `version` maps an item to its version
+    pub fn select<'a, T, F>(&self, items: &'a [T], version: F) -> Option<&'a T>
+    where
+        F: Fn(&T) -> &Version,
+    {
+        let newest = |pred: &dyn Fn(&Version) -> bool| {
+            items
+                .iter()
+                .filter(|x| pred(version(x)))
+                .max_by(|a, b| version(a).cmp(version(b)))
+        };
+        match self {
+            Self::Latest | Self::Stable => newest(&|v| v.pre.is_empty()),
+            Self::OldStable => {
+                let latest = version(newest(&|v| v.pre.is_empty())?);
+                newest(&|v| v.pre.is_empty() && (v.major, v.minor) < (latest.major, latest.minor))
+            }
+            Self::Rc => newest(&|v| !v.pre.is_empty()),
+            Self::Exact(e) => items.iter().find(|x| version(x) == e),
+            Self::Req(r) => newest(&|v| r.matches(v)),
+        }
+    }
+    /// Every release in the list the spec covers, ranges take all their matches instead of the newest
+    pub fn resolve_all(&self, versions: &GoVersions) -> Result<Vec<GoVersion>> {
+        let res: Vec<GoVersion> = match self {
+            Self::Req(r) => versions
+                .versions
+                .iter()
+                .filter(|x| r.matches(&x.parsed))
+                .cloned()
+                .collect(),
+            _ => vec![self.resolve(versions)?],
+        };
+        if res.is_empty() {
+            return Err(anyhow!("No version matching {} found", self));
+        }
+        Ok(res)
+    }
+    /// Resolves the spec against the remote version list
+    pub fn resolve(&self, versions: &GoVersions) -> Result<GoVersion> {
+        let res = match self {
+            Self::Latest | Self::Stable => Some(versions.latest()?),
+            Self::OldStable => {
+                let latest = versions.latest()?.parsed;
+                latest
+                    .minor
+                    .checked_sub(1)
+                    .and_then(|minor| versions.latest_for_minor(latest.major, minor))
+                    .cloned()
+            }
+            Self::Rc => versions.latest_prerelease().cloned(),
+            _ => self.select(&versions.versions, |x| &x.parsed).cloned(),
+        };
+        res.with_context(|| format!("No version matching {} found", self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn spec(s: &str) -> VersionSpec {
+        s.parse().unwrap()
+    }
+
+    fn versions() -> GoVersions {
+        let rels = [
+            "go1.23rc1",
+            "go1.22.1",
+            "go1.22.0",
+            "go1.21.5",
+            "go1.21.0",
+            "go1.20",
+        ]
+        .iter()
+        .map(|x| {
+            serde_json::from_value(serde_json::json!({
+                "version": x,
+                "stable": !x.contains("rc"),
+                "files": [],
+            }))
+            .unwrap()
+        })
+        .collect();
+        GoVersions::from_releases(PathBuf::new(), rels).unwrap()
+    }
+
+    fn resolved(s: &str) -> String {
+        spec(s).resolve(&versions()).unwrap().version
+    }
+
+    #[test]
+    fn parses_specs() {
+        assert_eq!(spec("latest"), VersionSpec::Latest);
+        assert_eq!(spec("Stable"), VersionSpec::Stable);
+        assert_eq!(spec("oldstable"), VersionSpec::OldStable);
+        assert_eq!(spec("rc"), VersionSpec::Rc);
+        assert_eq!(spec("go1.21.5"), VersionSpec::Exact(Version::new(1, 21, 5)));
+        assert_eq!(
+            spec("1.22rc1"),
+            VersionSpec::Exact(Version::parse("1.22.0-rc.1").unwrap())
+        );
+        assert_eq!(
+            spec("1.21"),
+            VersionSpec::Req(VersionReq::parse("~1.21").unwrap())
+        );
+        assert_eq!(
+            spec("go1"),
+            VersionSpec::Req(VersionReq::parse("~1").unwrap())
+        );
+        assert_eq!(
+            spec("1.21.x"),
+            VersionSpec::Req(VersionReq::parse("1.21.x").unwrap())
+        );
+        assert_eq!(
+            spec(">=1.20, <1.22"),
+            VersionSpec::Req(VersionReq::parse(">=1.20, <1.22").unwrap())
+        );
+        assert!("1.x.y".parse::<VersionSpec>().is_err());
+        assert!("~foo".parse::<VersionSpec>().is_err());
+    }
+
+    #[test]
+    fn resolves_against_the_list() {
+        assert_eq!(resolved("latest"), "go1.22.1");
+        assert_eq!(resolved("stable"), "go1.22.1");
+        assert_eq!(resolved("oldstable"), "go1.21.5");
+        assert_eq!(resolved("rc"), "go1.23rc1");
+        assert_eq!(resolved("1.21.0"), "go1.21.0");
+        assert_eq!(resolved("1.21"), "go1.21.5");
+        assert_eq!(resolved("~1.20"), "go1.20");
+        assert_eq!(resolved("1.22.x"), "go1.22.1");
+        assert_eq!(resolved(">=1.20, <1.22"), "go1.21.5");
+        assert!(spec("1.19").resolve(&versions()).is_err());
+        assert!(spec("1.21.3").resolve(&versions()).is_err());
+    }
+
+    #[test]
+    fn resolves_every_match_of_a_range() {
+        let all: Vec<String> = spec("1.21")
+            .resolve_all(&versions())
+            .unwrap()
+            .into_iter()
+            .map(|x| x.version)
+            .collect();
+        assert_eq!(all, ["go1.21.5", "go1.21.0"]);
+        assert_eq!(spec("latest").resolve_all(&versions()).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn selects_from_installed() {
+        let installed: Vec<Version> = ["1.20.14", "1.21.5", "1.21.0", "1.22.0-rc.1"]
+            .iter()
+            .map(|x| Version::parse(x).unwrap())
+            .collect();
+        let select = |s: &str| spec(s).select(&installed, |x| x).map(|x| x.to_string());
+        assert_eq!(select("latest").unwrap(), "1.21.5");
+        assert_eq!(select("oldstable").unwrap(), "1.20.14");
+        assert_eq!(select("rc").unwrap(), "1.22.0-rc.1");
+        assert_eq!(select("1.21").unwrap(), "1.21.5");
+        assert_eq!(select("go1.21.0").unwrap(), "1.21.0");
+        assert_eq!(select("1.20.x").unwrap(), "1.20.14");
+        assert!(select("1.19").is_none());
+    }
+}